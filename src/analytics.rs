@@ -1,10 +1,15 @@
+use std::collections::VecDeque;
 use std::fs::OpenOptions;
+use std::sync::Mutex;
 use std::io::{Write, BufWriter};
 use chrono::Local;
 use std::fs;
 use std::{ thread, time::Duration};
 use systemstat::{System, Platform};
 
+static CPU_SAMPLES: Mutex<VecDeque<f32>> = Mutex::new(VecDeque::new()); // ultimi campioni (uno al minuto)
+const MAX_CPU_SAMPLES: usize = 60;
+
 //// Funzione per inizializzare il file di log e scrivere l'intestazione se necessario
 fn initialize_log_file(log_file: &str, header: &str) -> std::io::Result<BufWriter<std::fs::File>> {
     // Apre o crea il file CSV
@@ -64,6 +69,98 @@ pub fn log_backup_data_to_csv(total_size: u64, duration: u64, cpu_usage: f32) {
     }
 }
 
+/// Funzione per registrare l'esito di una verifica (scrub) dei backup salvati
+pub fn log_scrub_result_to_csv(checked: usize, corrupted: &[String]) {
+    let log_file = "scrub_log.csv"; // Percorso del file CSV
+    let header = "Timestamp, File Verificati, File Corrotti, Percorsi Corrotti";
+
+    // Inizializza il file di log
+    let mut writer = match initialize_log_file(log_file, header) {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Errore durante l'apertura o la creazione del file di log: {}", e);
+            return;
+        }
+    };
+
+    // I percorsi possono contenere virgole: vengono separati da " | " e privati delle virgole
+    let paths: Vec<String> = corrupted.iter().map(|p| p.replace(',', " ")).collect();
+    let result = writeln!(
+        writer,
+        "{}, {}, {}, {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        checked,
+        corrupted.len(),
+        paths.join(" | ")
+    );
+
+    if let Err(e) = result {
+        eprintln!("Errore durante la scrittura dei dati nel file di log: {}", e);
+    }
+
+    if let Err(e) = writer.flush() {
+        eprintln!("Errore durante il flush del file di log: {}", e);
+    }
+}
+
+/// Restituisce il timestamp dell'ultima verifica registrata, se presente
+pub fn last_scrub_timestamp() -> Option<chrono::NaiveDateTime> {
+    let data = fs::read_to_string("scrub_log.csv").ok()?;
+    let last = data.lines().skip(1).last()?;
+    let timestamp = last.split(',').next()?.trim();
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S").ok()
+}
+
+fn record_cpu_sample(load: f32) {
+    let mut samples = CPU_SAMPLES.lock().unwrap();
+    samples.push_back(load);
+    if samples.len() > MAX_CPU_SAMPLES {
+        samples.pop_front();
+    }
+}
+
+/// Carico medio della CPU negli ultimi `minutes` minuti (None se non ci sono ancora abbastanza campioni)
+pub fn recent_cpu_average(minutes: usize) -> Option<f32> {
+    let samples = CPU_SAMPLES.lock().unwrap();
+    let minutes = minutes.max(1);
+    if samples.len() < minutes {
+        return None;
+    }
+    Some(samples.iter().rev().take(minutes).sum::<f32>() / minutes as f32)
+}
+
+/// Funzione per registrare di quanto è stato rimandato un job in attesa di un carico della CPU basso.
+/// Il ritardo ha un file a parte, mostrato nel pannello Analytics: `backup_log.csv` mantiene le sue quattro colonne.
+pub fn log_job_delay_to_csv(job: &str, delay_secs: u64, reason: &str) {
+    let log_file = "job_delay_log.csv"; // Percorso del file CSV
+    let header = "Timestamp, Job, Ritardo (s), Motivo Avvio";
+
+    let mut writer = match initialize_log_file(log_file, header) {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Errore durante l'apertura o la creazione del file di log: {}", e);
+            return;
+        }
+    };
+
+    let result = writeln!(
+        writer,
+        "{}, {}, {}, {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        job.replace(',', " "),
+        delay_secs,
+        reason
+    );
+
+    if let Err(e) = result {
+        eprintln!("Errore durante la scrittura dei dati nel file di log: {}", e);
+    }
+
+    if let Err(e) = writer.flush() {
+        eprintln!("Errore durante il flush del file di log: {}", e);
+    }
+}
+
 /// Funzione per registrare il consumo di CPU in un file CSV ogni minuto
 pub fn log_cpu_usage_to_csv() {
     let log_file = "cpu_usage_log.csv"; // Percorso del file CSV
@@ -90,6 +187,7 @@ pub fn log_cpu_usage_to_csv() {
 
                 match load.done() {
                     Ok(load) => {
+                        record_cpu_sample(load.user * 100.0);
                         // Scrive i dettagli nel file CSV ogni minuto
                         writeln!(
                             writer,
@@ -112,4 +210,66 @@ pub fn log_cpu_usage_to_csv() {
         // Aspetta un minuto prima di registrare di nuovo
         thread::sleep(Duration::from_secs(60)); // Aspetta 60 secondi prima di ripetere
     }
-}
\ No newline at end of file
+}
+/// Registra l'avanzamento del backup finale allo spegnimento (un evento per riga),
+/// così dopo il riavvio si vede quali file sono stati salvati in tempo
+pub fn log_shutdown_backup_to_csv(event: &str, detail: &str) {
+    let log_file = "shutdown_backup_log.csv"; // Percorso del file CSV
+    let header = "Timestamp, Evento, Dettaglio";
+
+    let mut writer = match initialize_log_file(log_file, header) {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Errore durante l'apertura o la creazione del file di log: {}", e);
+            return;
+        }
+    };
+
+    // Il sistema può spegnersi in qualsiasi momento: ogni riga viene scritta subito
+    let result = writeln!(
+        writer,
+        "{}, {}, {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        event,
+        detail.replace(',', " ")
+    );
+
+    if let Err(e) = result {
+        eprintln!("Errore durante la scrittura dei dati nel file di log: {}", e);
+    }
+
+    if let Err(e) = writer.flush() {
+        eprintln!("Errore durante il flush del file di log: {}", e);
+    }
+}
+
+/// Registra i backup automatici annullati o trattenuti dal controllo delle modifiche di massa,
+/// perché nessuno era presente a confermare di voler proseguire
+pub fn log_guard_event_to_csv(event: &str, detail: &str) {
+    let log_file = "guard_log.csv"; // Percorso del file CSV
+    let header = "Timestamp, Evento, Dettaglio";
+
+    let mut writer = match initialize_log_file(log_file, header) {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Errore durante l'apertura o la creazione del file di log: {}", e);
+            return;
+        }
+    };
+
+    let result = writeln!(
+        writer,
+        "{}, {}, {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        event,
+        detail.replace(',', " ")
+    );
+
+    if let Err(e) = result {
+        eprintln!("Errore durante la scrittura dei dati nel file di log: {}", e);
+    }
+
+    if let Err(e) = writer.flush() {
+        eprintln!("Errore durante il flush del file di log: {}", e);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::analytics::log_backup_data_to_csv;
+use crate::detector::BackupLauncher;
+use crate::power;
+use crate::shutdown;
+use crate::transfer::get_cpu_usage;
+use crate::utils::{load_config, play_sound, speak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Controlla la batteria e, quando scende sotto il livello critico mentre si scarica, avvia da solo
+/// un backup di emergenza a tempo dei file più importanti, prima che il portatile si spenga
+pub fn run_battery_watch(launcher: BackupLauncher) {
+    let mut fired = false; // backup già fatto in questa scarica
+
+    loop {
+        thread::sleep(Duration::from_secs(30));
+        let Some(settings) = load_config().map(|config| config.battery_emergency) else {
+            continue;
+        };
+        let state = power::power_state();
+        let Some(charge) = state.charge else {
+            continue;
+        };
+        if !state.on_battery {
+            // Alimentatore ricollegato: la prossima scarica avrà il suo backup
+            fired = false;
+            continue;
+        }
+        if !settings.enabled || fired || charge >= settings.critical_percent {
+            continue;
+        }
+        // Un backup già in corso (es. avviato con il gesto) copre l'emergenza. Finché il launcher
+        // resta riservato il detector è disattivato e gli altri trigger non avviano nulla.
+        if !launcher.try_claim() {
+            continue;
+        }
+        fired = true;
+
+        play_sound("Sounds/system-notification-199277.mp3");
+        speak(&format!("Battery at {:.0} percent. Starting emergency backup.", charge));
+        println!("Batteria critica ({:.0}%): backup di emergenza.", charge);
+        play_sound("Sounds/bubblepop-254773.mp3");
+
+        let start_time = Instant::now();
+        let log = |event: &str, detail: &str| println!("Backup di emergenza: {} {}", event, detail);
+        let result = shutdown::quick_backup(
+            Duration::from_secs(settings.time_budget_secs),
+            &settings.priority_folders,
+            &log,
+        );
+        match result {
+            Ok(files) => {
+                let copied: u64 = files.iter().map(|entry| entry.size).sum();
+                log_backup_data_to_csv(copied, start_time.elapsed().as_secs(), get_cpu_usage());
+                play_sound("Sounds/bellding-254774.mp3");
+                speak(&format!("Emergency backup finished. {} files saved.", files.len()));
+            }
+            Err(e) => {
+                eprintln!("Backup di emergenza fallito: {}", e);
+                play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+            }
+        }
+        launcher.release();
+    }
+}
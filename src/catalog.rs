@@ -0,0 +1,241 @@
+use crate::manifest::{self, RunManifest};
+use crate::storage::{self, LocalStorage, StorageBackend};
+use chrono::{Local, NaiveDate, TimeZone};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+static CATALOG_PATH: &str = "catalog.db"; // Database con tutti i file salvati dai backup
+
+/// File trovato nel catalogo
+#[derive(Serialize, Clone, Debug)]
+pub struct CatalogEntry {
+    pub run_id: String,
+    pub destination: String,
+    pub path: String,
+    pub size: u64,
+    pub modified: u64,
+    pub hash: String,
+    // Falso se la versione è stata sostituita da un backup successivo senza che ne restasse una copia
+    // (backup fatti prima che le versioni sostituite venissero conservate)
+    pub available: bool,
+}
+
+/// Criteri di ricerca inseriti dall'utente nel pannello del catalogo
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct CatalogQuery {
+    pub name: String,
+    pub extension: String,
+    pub from: String, // data nel formato YYYY-MM-DD (vuota = nessun limite)
+    pub to: String,
+}
+
+fn open_catalog() -> rusqlite::Result<Connection> {
+    let conn = Connection::open(CATALOG_PATH)?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS runs (
+            run_id TEXT NOT NULL,
+            destination TEXT NOT NULL,
+            source_folder TEXT NOT NULL,
+            PRIMARY KEY (run_id, destination)
+        );
+        CREATE TABLE IF NOT EXISTS files (
+            run_id TEXT NOT NULL,
+            destination TEXT NOT NULL,
+            path TEXT NOT NULL,
+            name TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            modified INTEGER NOT NULL,
+            hash TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS files_name ON files (name);
+        CREATE INDEX IF NOT EXISTS files_path ON files (destination, path, run_id);
+        CREATE TABLE IF NOT EXISTS objects (
+            destination TEXT NOT NULL,
+            hash TEXT NOT NULL,
+            PRIMARY KEY (destination, hash)
+        );",
+    )?;
+    Ok(conn)
+}
+
+/// Registra nel catalogo tutti i file di un backup; `destination` è la descrizione della destinazione
+pub fn record_run(destination: &str, manifest: &RunManifest) -> rusqlite::Result<()> {
+    let mut conn = open_catalog()?;
+    let tx = conn.transaction()?;
+    let inserted = tx.execute(
+        "INSERT OR IGNORE INTO runs (run_id, destination, source_folder) VALUES (?1, ?2, ?3)",
+        params![manifest.run_id, destination, manifest.source_folder],
+    )?;
+    // Backup già registrato: i suoi file sono già nel catalogo e non vanno duplicati
+    if inserted == 0 {
+        return Ok(());
+    }
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO files (run_id, destination, path, name, size, modified, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        for entry in &manifest.files {
+            let name = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+            insert.execute(params![
+                manifest.run_id,
+                destination,
+                entry.path,
+                name.to_lowercase(),
+                entry.size as i64,
+                entry.modified as i64,
+                entry.hash
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Registra che la destinazione conserva in `.group24/objects` la versione con questo hash
+pub fn record_object(destination: &str, hash: &str) -> rusqlite::Result<()> {
+    let conn = open_catalog()?;
+    conn.execute(
+        "INSERT OR IGNORE INTO objects (destination, hash) VALUES (?1, ?2)",
+        params![destination, hash],
+    )?;
+    Ok(())
+}
+
+/// Importa nel catalogo i backup della destinazione che non sono ancora registrati
+/// (ad esempio quelli creati prima dell'introduzione del catalogo)
+pub fn sync_destination(backend: &dyn StorageBackend) -> rusqlite::Result<()> {
+    let conn = open_catalog()?;
+    let destination_str = backend.describe();
+    for run_id in manifest::list_runs(backend) {
+        let known: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM runs WHERE run_id = ?1 AND destination = ?2",
+            params![run_id, destination_str],
+            |row| row.get(0),
+        )?;
+        if known == 0 {
+            if let Ok(run) = manifest::read_manifest(backend, &run_id) {
+                record_run(&destination_str, &run)?;
+            }
+        }
+    }
+    // Versioni sostituite conservate nella destinazione (anche da altri computer)
+    if let Ok(objects) = backend.list(manifest::OBJECTS_DIR) {
+        for object in objects {
+            if let Some(hash) = object.path.rsplit('/').next() {
+                record_object(&destination_str, hash)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Converte una data YYYY-MM-DD nel timestamp locale di inizio giornata
+fn day_start(date: &str) -> Result<Option<i64>, String> {
+    let date = date.trim();
+    if date.is_empty() {
+        return Ok(None);
+    }
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date `{}`: use the format YYYY-MM-DD.", date))?;
+    let start = day.and_hms_opt(0, 0, 0).unwrap();
+    Ok(Local
+        .from_local_datetime(&start)
+        .earliest()
+        .map(|time| time.timestamp()))
+}
+
+/// Rende letterali `%` e `_` (e il carattere di escape) nel testo cercato, altrimenti LIKE li
+/// interpreterebbe come caratteri jolly
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Cerca nel catalogo per nome, estensione e intervallo di date di modifica
+pub fn search(query: &CatalogQuery) -> Result<Vec<CatalogEntry>, String> {
+    let from = day_start(&query.from)?;
+    // L'estremo superiore è incluso: si arriva fino alla fine del giorno indicato
+    let to = day_start(&query.to)?.map(|t| t + 24 * 60 * 60);
+    let name = format!("%{}%", escape_like(&query.name.trim().to_lowercase()));
+    let extension = query.extension.trim().trim_start_matches('.').to_lowercase();
+    let extension = if extension.is_empty() {
+        "%".to_string()
+    } else {
+        format!("%.{}", escape_like(&extension))
+    };
+
+    let conn = open_catalog().map_err(|e| e.to_string())?;
+    let mut statement = conn
+        .prepare(
+            // Una versione è ripristinabile se è ancora quella nella destinazione (la più recente
+            // registrata per quel percorso) o se ne è stata conservata una copia tra gli oggetti
+            "SELECT f.run_id, f.destination, f.path, f.size, f.modified, f.hash,
+                f.hash = (SELECT l.hash FROM files l WHERE l.destination = f.destination AND l.path = f.path
+                          ORDER BY l.run_id DESC LIMIT 1)
+                OR EXISTS (SELECT 1 FROM objects o WHERE o.destination = f.destination AND o.hash = f.hash)
+             FROM files f
+             WHERE f.name LIKE ?1 ESCAPE '\\' AND f.name LIKE ?2 ESCAPE '\\' AND f.modified >= ?3 AND f.modified < ?4
+             ORDER BY f.modified DESC, f.run_id DESC LIMIT 200",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(
+            params![name, extension, from.unwrap_or(0), to.unwrap_or(i64::MAX)],
+            |row| {
+                Ok(CatalogEntry {
+                    run_id: row.get(0)?,
+                    destination: row.get(1)?,
+                    path: row.get(2)?,
+                    size: row.get::<_, i64>(3)? as u64,
+                    modified: row.get::<_, i64>(4)? as u64,
+                    hash: row.get(5)?,
+                    available: row.get(6)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+}
+
+/// Apre la destinazione in cui è salvato un file del catalogo
+fn open_entry_destination(entry: &CatalogEntry) -> Result<Box<dyn StorageBackend>, String> {
+    if let Ok(backend) = storage::open_configured() {
+        if backend.describe() == entry.destination {
+            return Ok(backend);
+        }
+    }
+    // Backup salvati in una destinazione diversa da quella attuale: si prova come cartella locale
+    Ok(Box::new(LocalStorage::open(Path::new(&entry.destination))?))
+}
+
+/// Ripristina un file trovato nel catalogo nella cartella scelta dall'utente
+pub fn restore(entry: &CatalogEntry, target_folder: &Path) -> Result<(), String> {
+    let backend = open_entry_destination(entry)?;
+    // La destinazione contiene l'ultima copia di ogni file: l'hash dice se è ancora la versione cercata,
+    // altrimenti si usa la copia conservata quando un backup successivo l'ha sostituita
+    let stored = match storage::hash_stored(backend.as_ref(), &entry.path) {
+        Ok(hash) if hash == entry.hash => entry.path.clone(),
+        _ if backend.stat(&manifest::object_path(&entry.hash)).ok().flatten().is_some() => {
+            manifest::object_path(&entry.hash)
+        }
+        Ok(_) => {
+            return Err(format!(
+                "The version of `{}` saved by backup {} has been replaced by a later backup.",
+                entry.path, entry.run_id
+            ))
+        }
+        Err(e) => return Err(format!("Unable to read `{}`: {}", entry.path, e)),
+    };
+
+    let name = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+    let copy = || -> io::Result<()> {
+        let mut reader = backend.get(&stored)?;
+        let mut file = File::create(target_folder.join(name))?;
+        io::copy(&mut reader, &mut file)?;
+        Ok(())
+    };
+    copy().map_err(|e| format!("Restore failed: {}", e))
+}
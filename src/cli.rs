@@ -0,0 +1,114 @@
+use crate::diff;
+use crate::manifest;
+use crate::peer;
+use crate::replication;
+use crate::storage::{self, LocalStorage, StorageBackend};
+use crate::utils::load_config;
+use std::path::Path;
+
+const USAGE: &str = "Usage:
+  backup_app_group24 runs [destination]
+  backup_app_group24 diff <old backup> <new backup> [destination]
+  backup_app_group24 receive <folder> [port]
+  backup_app_group24 replicate [target folder]";
+
+/// Destinazione passata come argomento (cartella locale) o, in sua assenza, quella configurata
+fn destination_arg(arg: Option<&String>) -> Result<Box<dyn StorageBackend>, String> {
+    match arg {
+        Some(path) => Ok(Box::new(LocalStorage::open(Path::new(path))?)),
+        None => storage::open_configured(),
+    }
+}
+
+/// Esegue un comando da riga di comando e restituisce il codice di uscita
+pub fn run(args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        Some("runs") => {
+            let backend = match destination_arg(args.get(1)) {
+                Ok(backend) => backend,
+                Err(e) => {
+                    eprintln!("{}\n{}", e, USAGE);
+                    return 2;
+                }
+            };
+            for run_id in manifest::list_runs(backend.as_ref()) {
+                println!("{}", run_id);
+            }
+            0
+        }
+        Some("diff") if args.len() >= 3 => {
+            let backend = match destination_arg(args.get(3)) {
+                Ok(backend) => backend,
+                Err(e) => {
+                    eprintln!("{}\n{}", e, USAGE);
+                    return 2;
+                }
+            };
+            match diff::diff_stored_runs(backend.as_ref(), &args[1], &args[2]) {
+                Ok(result) => {
+                    println!("{}", result.to_text());
+                    0
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+        Some("replicate") => {
+            // Senza argomento la destinazione è quella della sezione [replication]
+            let config = load_config().unwrap_or_default();
+            let backends = storage::open_destination(&config).and_then(|source| {
+                let target: Box<dyn StorageBackend> = match args.get(1) {
+                    Some(path) => Box::new(LocalStorage::open(Path::new(path))?),
+                    None if config.replication.destination_type.is_empty() => {
+                        return Err("No replication target: add a [replication] section to config_build.toml.".to_string())
+                    }
+                    None => storage::open_extra(&config, &config.replication)?,
+                };
+                Ok((source, target))
+            });
+            let (source, target) = match backends {
+                Ok(backends) => backends,
+                Err(e) => {
+                    eprintln!("{}\n{}", e, USAGE);
+                    return 2;
+                }
+            };
+            let mut progress = |value: f32, current: &str| println!("[{:3.0}%] {}", value * 100.0, current);
+            match replication::replicate(source.as_ref(), target.as_ref(), &|| false, &mut progress) {
+                Ok(report) => {
+                    println!("{}", report.summary_lines().join("\n"));
+                    0
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+        Some("receive") if args.len() >= 2 => {
+            // Chiave e porta predefinita vengono dalla sezione [peer] della configurazione
+            let config = load_config().unwrap_or_default();
+            let port = match args.get(2).map(|port| port.parse::<u16>()) {
+                Some(Ok(port)) => port,
+                Some(Err(_)) => {
+                    eprintln!("Invalid port.\n{}", USAGE);
+                    return 2;
+                }
+                None => config.peer.port,
+            };
+            match peer::run_receiver(Path::new(&args[1]), port, &config.peer.key) {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            }
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::analytics::{log_job_delay_to_csv, recent_cpu_average};
+use crate::utils::load_config;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Rimanda i job non urgenti finché il carico medio della CPU non resta basso per qualche minuto,
+/// al massimo per `max_wait_minutes`; usata dallo scheduler e dal trigger di inattività
+#[derive(Default)]
+pub struct CpuGate {
+    waiting: HashMap<String, Instant>, // job in attesa -> momento in cui sarebbe dovuto partire
+}
+
+impl CpuGate {
+    /// Vero se il job `key` può partire ora. Quando parte, il ritardo accumulato viene registrato
+    /// in `job_delay_log.csv`.
+    pub fn ready(&mut self, key: &str) -> bool {
+        let Some(settings) = load_config().map(|config| config.cpu_gate) else {
+            return true;
+        };
+        if !settings.enabled {
+            self.waiting.remove(key);
+            return true;
+        }
+
+        let since = *self.waiting.entry(key.to_string()).or_insert_with(Instant::now);
+        let waited = since.elapsed();
+        // Senza abbastanza campioni (es. subito dopo l'avvio) il carico non è noto e si aspetta
+        let low_load = recent_cpu_average(settings.window_minutes)
+            .is_some_and(|average| average < settings.max_load_percent);
+        let timed_out = waited >= Duration::from_secs(settings.max_wait_minutes * 60);
+        if !low_load && !timed_out {
+            return false;
+        }
+
+        self.waiting.remove(key);
+        let reason = if low_load { "low load" } else { "max wait reached" };
+        log_job_delay_to_csv(key, waited.as_secs(), reason);
+        true
+    }
+
+    /// Il job non è più in attesa (es. l'utente è tornato prima dell'avvio)
+    pub fn cancel(&mut self, key: &str) {
+        self.waiting.remove(key);
+    }
+}
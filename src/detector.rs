@@ -1,14 +1,23 @@
 use crate::confirm_sign;
 use crate::first_sign;
+use crate::hotkey::Hotkeys;
+use crate::idle;
+use crate::queue;
+use crate::replication;
+use crate::schedule;
 use crate::transfer::perform_backup_with_stop;
 use crate::ui::BackupStatus;
-use crate::ui::MyApp;
+use crate::ui::{AppState, JobKind, MyApp};
 use crate::utils;
-use rdev::{listen, Button, EventType};
+use rdev::{listen, Button, EventType, Key};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Receiver;
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+static COUNTDOWN_ACTIVE: AtomicBool = AtomicBool::new(false); // conto alla rovescia prima del backup in corso
 
 #[derive(Debug)]
 struct ScreenResolution {
@@ -21,6 +30,7 @@ pub fn avvia_backup(
     detector_running: Arc<AtomicBool>,
     tx: Sender<String>,
     stop_rx: Arc<Mutex<Receiver<String>>>, // Receiver incapsulato
+    unattended: bool,                      // avviato senza il gesto: nessuno potrebbe confermare gli avvisi
 ) {
     // Disattiva il detector
     detector_running.store(false, Ordering::Relaxed);
@@ -32,27 +42,37 @@ pub fn avvia_backup(
             let state = shared_state.lock().unwrap();
             let mut state = state.state.lock().unwrap();
             state.backup_status = BackupStatus::InProgress;
+            state.job = JobKind::Backup;
         }
 
         // Esegui il backup con controllo di stop
         // Sblocca il Mutex per ottenere il Receiver e passa la referenza
         let backup_result = {
             let stop_rx = stop_rx.lock().unwrap(); // Sblocca il Mutex
-            let mut state = shared_state.lock().unwrap();
-            perform_backup_with_stop(&*stop_rx, &mut state) // Passa una referenza al Receiver
+            // Scarta eventuali comandi rimasti nel canale (es. una pausa arrivata a backup concluso)
+            while stop_rx.try_recv().is_ok() {}
+            // Si lavora su una copia (stessi canali e stesso stato): `shared_state` non resta bloccato
+            // durante il backup e il gesto funziona anche con un backup per inattività in pausa
+            let mut state = shared_state.lock().unwrap().clone();
+            perform_backup_with_stop(&*stop_rx, &mut state, unattended) // Passa una referenza al Receiver
         };
         match backup_result {
-            Ok(_) => {
+            Ok(report) => {
                 let state = shared_state.lock().unwrap();
                 let mut state = state.state.lock().unwrap();
+                state.last_report = Some(report);
                 state.backup_status = BackupStatus::CompletedSuccess;
+                schedule::record_success(&JobKind::Backup);
                 println!("Backup completato con successo.");
             }
             Err(err) => {
                 let state = shared_state.lock().unwrap();
                 let mut state = state.state.lock().unwrap();
                 if err == "stop" {
-                    state.backup_status = BackupStatus::NotStarted;
+                    // Il gesto che ha annullato un backup in pausa può aver già chiesto una conferma
+                    if matches!(state.backup_status, BackupStatus::InProgress | BackupStatus::Canceled) {
+                        state.backup_status = BackupStatus::NotStarted;
+                    }
                     println!("Backup interrotto dall'utente.");
                 } else {
                     state.backup_status = BackupStatus::CompletedError(err);
@@ -85,6 +105,289 @@ pub fn avvia_backup(
     println!("Il backup è stato avviato in un thread separato.");
 }
 
+/// Avvia la replica dei backup verso la destinazione di [replication], come job separato
+/// che usa la stessa finestra di avanzamento del backup (tramite `BackupLauncher::start_job`)
+fn avvia_replica(
+    shared_state: Arc<Mutex<MyApp>>,
+    detector_running: Arc<AtomicBool>,
+    tx: Sender<String>,
+    stop_rx: Arc<Mutex<Receiver<String>>>,
+) {
+    detector_running.store(false, Ordering::Relaxed);
+
+    std::thread::spawn(move || {
+        {
+            let state = shared_state.lock().unwrap();
+            let mut state = state.state.lock().unwrap();
+            state.backup_status = BackupStatus::InProgress;
+            state.job = JobKind::Replication;
+        }
+
+        let result = {
+            let stop_rx = stop_rx.lock().unwrap();
+            // Scarta eventuali comandi rimasti nel canale da operazioni precedenti
+            while stop_rx.try_recv().is_ok() {}
+            let mut state = shared_state.lock().unwrap().clone();
+            replication::perform_replication_with_stop(&stop_rx, &mut state)
+        };
+        {
+            let state = shared_state.lock().unwrap();
+            let mut state = state.state.lock().unwrap();
+            match result {
+                Ok(report) => {
+                    state.last_replication = Some(report);
+                    state.backup_status = BackupStatus::CompletedSuccess;
+                    schedule::record_success(&JobKind::Replication);
+                    println!("Replica completata con successo.");
+                }
+                Err(err) if err == "stop" => {
+                    state.backup_status = BackupStatus::NotStarted;
+                    println!("Replica interrotta dall'utente.");
+                }
+                Err(err) => {
+                    eprintln!("Replica fallita: {}", err);
+                    state.backup_status = BackupStatus::CompletedError(err);
+                }
+            }
+            if !state.display {
+                if let Err(err) = tx.send("showGUI".to_string()) {
+                    eprintln!("Failed to send showGUI message: {}", err);
+                    state.display = false;
+                }
+            }
+        }
+        detector_running.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Tutto il necessario per avviare un backup senza il gesto (collegamento del volume, coda, ...)
+#[derive(Clone)]
+pub struct BackupLauncher {
+    pub app: Arc<Mutex<MyApp>>,
+    pub state: Arc<Mutex<AppState>>, // stesso stato di `app`, ma accessibile anche durante il backup
+    pub detector_running: Arc<AtomicBool>,
+    pub tx: Sender<String>,
+    pub rx_stop: Arc<Mutex<Receiver<String>>>,
+}
+
+/// Stati in cui non c'è nessun job attivo: l'esito dell'ultimo resta nella finestra finché
+/// l'utente non torna indietro, ma non deve bloccare i backup automatici successivi
+fn no_job_active(status: &BackupStatus) -> bool {
+    matches!(
+        status,
+        BackupStatus::NotStarted
+            | BackupStatus::CompletedSuccess
+            | BackupStatus::CompletedError(_)
+            | BackupStatus::Canceled
+    )
+}
+
+impl BackupLauncher {
+    /// Vero se non c'è un backup in corso o in attesa di conferma
+    pub fn is_idle(&self) -> bool {
+        no_job_active(&self.state.lock().unwrap().backup_status) && self.detector_running.load(Ordering::Relaxed)
+    }
+
+    /// Avvia il backup mostrando la GUI; restituisce false se un altro backup è già in corso
+    pub fn start(&self, reason: &str) -> bool {
+        self.start_job(JobKind::Backup, reason)
+    }
+
+    /// Riserva il launcher per un job: controllo e disattivazione del detector avvengono sotto
+    /// il lock dello stato, così due trigger non possono avviare un job nello stesso momento
+    fn claim(&self, state: &AppState) -> bool {
+        no_job_active(&state.backup_status)
+            && self
+                .detector_running
+                .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+    }
+
+    /// Riserva il launcher per un job eseguito fuori dalla finestra di avanzamento (es. il backup
+    /// di emergenza a batteria scarica); restituisce false se un altro job è già attivo.
+    /// Va seguita da `release` al termine.
+    pub fn try_claim(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        self.claim(&state)
+    }
+
+    /// Libera il launcher riservato con `try_claim`
+    pub fn release(&self) {
+        self.detector_running.store(true, Ordering::SeqCst);
+    }
+
+    /// Avvia un job (backup o replica) nella finestra di avanzamento
+    pub fn start_job(&self, job: JobKind, reason: &str) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            if !self.claim(&state) {
+                println!("Backup automatico ({}) ignorato: un backup è già in corso.", reason);
+                return false;
+            }
+            // Lo stato cambia subito, prima che il thread del job parta
+            state.backup_status = BackupStatus::InProgress;
+            state.job = job.clone();
+            println!("Avvio del backup automatico: {}.", reason);
+            if !state.display {
+                if let Err(err) = self.tx.send("showGUI".to_string()) {
+                    eprintln!("Failed to send showGUI message: {}", err);
+                    state.display = false;
+                } else {
+                    println!("Message sent to show GUI.");
+                }
+            }
+        }
+        let app = Arc::clone(&self.app);
+        let detector_running = Arc::clone(&self.detector_running);
+        let rx_stop = Arc::clone(&self.rx_stop);
+        match job {
+            JobKind::Backup => {
+                // Il backup copre anche quelli rimasti in coda
+                queue::clear();
+                avvia_backup(app, detector_running, self.tx.clone(), rx_stop, true);
+            }
+            JobKind::Replication => avvia_replica(app, detector_running, self.tx.clone(), rx_stop),
+        }
+        true
+    }
+}
+
+/// Un backup per inattività in pausa (l'utente è tornato) lascia attivo il detector: il gesto
+/// lo annulla e prende il suo posto
+fn cancel_paused_backup(shared_state: &Arc<Mutex<MyApp>>) {
+    let (app_state, tx_stop) = {
+        let app = shared_state.lock().unwrap();
+        (Arc::clone(&app.state), app.tx_stop.clone())
+    };
+    if !app_state.lock().unwrap().backup_paused {
+        return;
+    }
+    println!("Backup in pausa annullato dal gesto.");
+    if let Err(err) = tx_stop.send("stop".to_string()) {
+        eprintln!("Failed to send stop message to backup thread: {}", err);
+        return;
+    }
+    // Il thread del backup in pausa termina subito: si aspetta che concluda prima di proseguire
+    for _ in 0..50 {
+        if no_job_active(&app_state.lock().unwrap().backup_status) {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Primo segno riconosciuto (contorno dello schermo o combinazione di tasti): mostra la GUI
+/// e attende la conferma
+fn request_confirmation(shared_state: &Arc<Mutex<MyApp>>, tx: &Sender<String>) {
+    cancel_paused_backup(shared_state);
+    utils::play_sound("Sounds/system-notification-199277.mp3");
+    {
+        let state = shared_state.lock().unwrap();
+        let mut state = state.state.lock().unwrap();
+        state.backup_status = BackupStatus::ToConfirm;
+    }
+
+    {
+        let state = shared_state.lock().unwrap();
+        let mut state = state.state.lock().unwrap();
+        if !state.display {
+            if let Err(err) = tx.send("showGUI".to_string()) {
+                eprintln!("Failed to send message: {}", err);
+                state.display = false;
+            } else {
+                println!("Message sent successfully.");
+            }
+        } else {
+            println!("GUI already active. Skipping message.");
+        }
+    }
+}
+
+/// Conferma riconosciuta (linea orizzontale o combinazione di conferma): avvia il backup,
+/// dopo un conto alla rovescia durante il quale si può ancora annullare
+fn confirm_backup(
+    shared_state: &Arc<Mutex<MyApp>>,
+    detector_running: &Arc<AtomicBool>,
+    tx: &Sender<String>,
+    rx_stop: &Arc<Mutex<Receiver<String>>>,
+) {
+    {
+        let state = shared_state.lock().unwrap();
+        let mut state = state.state.lock().unwrap();
+        if !state.display {
+            if let Err(err) = tx.send("showGUI".to_string()) {
+                eprintln!("Failed to send message: {}", err);
+                state.display = false;
+            } else {
+                println!("Message sent successfully.");
+            }
+        } else {
+            println!("GUI already active. Skipping message.");
+        }
+    }
+
+    let seconds = utils::load_config().map(|config| config.countdown.seconds).unwrap_or(0);
+    if seconds == 0 {
+        // Disattiva il detector e avvia il backup
+        detector_running.store(false, Ordering::Relaxed);
+
+        avvia_backup(
+            Arc::clone(shared_state),
+            Arc::clone(detector_running),
+            tx.clone(),          // Aggiungi il trasmettitore
+            Arc::clone(rx_stop), // Passa il canale di stop al backup
+            false,
+        );
+        return;
+    }
+
+    // Il conto alla rovescia gira in un thread: il listener resta attivo per ricevere l'annullamento
+    let shared_state = Arc::clone(shared_state);
+    let detector_running = Arc::clone(detector_running);
+    let tx = tx.clone();
+    let rx_stop = Arc::clone(rx_stop);
+    COUNTDOWN_ACTIVE.store(true, Ordering::Relaxed);
+    set_backup_status(&shared_state, BackupStatus::Countdown(seconds));
+    std::thread::spawn(move || {
+        for remaining in (1..=seconds).rev() {
+            if !countdown_running(&shared_state) {
+                break;
+            }
+            set_backup_status(&shared_state, BackupStatus::Countdown(remaining));
+            utils::beep();
+            for _ in 0..10 {
+                std::thread::sleep(Duration::from_millis(100));
+                if !countdown_running(&shared_state) {
+                    break;
+                }
+            }
+        }
+        COUNTDOWN_ACTIVE.store(false, Ordering::Relaxed);
+
+        if !countdown_running(&shared_state) {
+            utils::play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+            println!("Backup annullato durante il conto alla rovescia.");
+            return;
+        }
+        println!("Conto alla rovescia terminato. Avvio del backup...");
+        detector_running.store(false, Ordering::Relaxed);
+        avvia_backup(shared_state, detector_running, tx, rx_stop, false);
+    });
+}
+
+fn set_backup_status(shared_state: &Arc<Mutex<MyApp>>, status: BackupStatus) {
+    let state = shared_state.lock().unwrap();
+    let mut state = state.state.lock().unwrap();
+    state.backup_status = status;
+}
+
+/// Falso se il conto alla rovescia è stato annullato (clic destro, Esc o pulsante della GUI)
+fn countdown_running(shared_state: &Arc<Mutex<MyApp>>) -> bool {
+    let state = shared_state.lock().unwrap();
+    let state = state.state.lock().unwrap();
+    matches!(state.backup_status, BackupStatus::Countdown(_))
+}
+
 pub fn run(
     shared_state: Arc<Mutex<MyApp>>,
     tx: Sender<String>,
@@ -117,11 +420,22 @@ pub fn run(
     let horizontal_line_tracker = Arc::new(Mutex::new(confirm_sign::HorizontalLineTracker::new()));
 
     let waiting_for_confirmation_clone = Arc::clone(&waiting_for_confirmation);
+    let replica_launcher = BackupLauncher {
+        app: Arc::clone(&shared_state),
+        state: Arc::clone(&shared_state.lock().unwrap().state),
+        detector_running: Arc::clone(&detector_running),
+        tx: tx.clone(),
+        rx_stop: Arc::clone(&rx_stop),
+    };
 
     // Thread separato per ascoltare i messaggi su `rx`
     std::thread::spawn(move || {
         while let Ok(msg) = rx.recv() {
-            if msg == "resetWaiting" {
+            if msg == "startReplication" {
+                // Richiesta dal Backup Panel: come ogni altro job parte solo se il launcher è libero,
+                // quindi non con un backup in corso né con una conferma o un conto alla rovescia in attesa
+                replica_launcher.start_job(JobKind::Replication, "requested from the Backup Panel");
+            } else if msg == "resetWaiting" {
                 println!(
                     "Ricevuto messaggio: resetWaiting. Imposto waiting_for_confirmation a false."
                 );
@@ -133,8 +447,23 @@ pub fn run(
 
     let segment_count = 20; // Numero di segmenti per lato
 
+    // Combinazioni di tasti alternative al gesto (schermo guasto o solo trackpad)
+    let mut hotkeys = Hotkeys::default();
+    hotkeys.refresh();
+    let mut pressed_keys: Vec<Key> = vec![];
+
     // Ogni volta che un evento si verifica, la closure viene chiamata
     if let Err(error) = listen(move |event| {
+        // Ogni input dell'utente azzera il tempo di inattività, anche a detector disattivato
+        idle::record_input();
+
+        // I tasti premuti vengono seguiti anche a detector disattivato, per non perdere i rilasci
+        match event.event_type {
+            EventType::KeyPress(key) if !pressed_keys.contains(&key) => pressed_keys.push(key),
+            EventType::KeyRelease(key) => pressed_keys.retain(|pressed| *pressed != key),
+            _ => {}
+        }
+
         // Controlla se il detector è attivo
         if !detector_running.load(Ordering::Relaxed) {
             //l'esecuzione del codice all'interno della closure viene interrotta solo per quell'evento
@@ -142,6 +471,37 @@ pub fn run(
             return;
         }
 
+        // Durante il conto alla rovescia un clic destro o Esc annullano il backup; il resto viene ignorato
+        if COUNTDOWN_ACTIVE.load(Ordering::Relaxed) {
+            if matches!(
+                event.event_type,
+                EventType::ButtonPress(Button::Right) | EventType::KeyPress(Key::Escape)
+            ) && countdown_running(&shared_state)
+            {
+                set_backup_status(&shared_state, BackupStatus::NotStarted);
+            }
+            return;
+        }
+
+        // Combinazioni di tasti: stesso flusso del gesto, con richiesta e conferma
+        hotkeys.refresh();
+        {
+            let mut waiting = waiting_for_confirmation.lock().unwrap();
+            if !*waiting {
+                if hotkeys.trigger.as_mut().is_some_and(|chord| chord.update(&pressed_keys)) {
+                    println!("Combinazione di tasti riconosciuta! Tieni premuta la combinazione di conferma per avviare il backup.");
+                    request_confirmation(&shared_state, &tx);
+                    *waiting = true;
+                    return;
+                }
+            } else if hotkeys.confirm.as_mut().is_some_and(|chord| chord.update(&pressed_keys)) {
+                println!("Combinazione di conferma riconosciuta! Avvio del backup...");
+                confirm_backup(&shared_state, &detector_running, &tx, &rx_stop);
+                *waiting = false;
+                return;
+            }
+        }
+
         match event.event_type {
             EventType::ButtonPress(Button::Left) => {
                 let mut tracking = tracking_active.lock().unwrap();
@@ -171,29 +531,8 @@ pub fn run(
                     let edges = edges_tracker.lock().unwrap();
 
                     if edges.is_contour_complete(screen_width, screen_height, segment_count) {
-                        utils::play_sound("Sounds/system-notification-199277.mp3");
-                        {
-                            let state = shared_state.lock().unwrap();
-                            let mut state = state.state.lock().unwrap();
-                            state.backup_status = BackupStatus::ToConfirm;
-                        }
                         println!("Contorno completo riconosciuto! Disegna una linea orizzontale per confermare e avviare il backup.");
-
-                        {
-                            let state = shared_state.lock().unwrap();
-                            let mut state = state.state.lock().unwrap();
-                            if !state.display {
-                                if let Err(err) = tx.send("showGUI".to_string()) {
-                                    eprintln!("Failed to send message: {}", err);
-                                    state.display = false;
-                                } else {
-                                    println!("Message sent successfully.");
-                                }
-                            } else {
-                                println!("GUI already active. Skipping message.");
-                            }
-                        }
-
+                        request_confirmation(&shared_state, &tx);
                         *waiting = true;
                     } else {
                         println!("Contorno non completo. Riprova disegnando il perimetro completo dello schermo.");
@@ -202,32 +541,7 @@ pub fn run(
                     let line_tracker = horizontal_line_tracker.lock().unwrap();
                     if line_tracker.is_valid_horizontal() {
                         println!("Linea orizzontale riconosciuta! Avvio del backup...");
-
-                        {
-                            let state = shared_state.lock().unwrap();
-                            let mut state = state.state.lock().unwrap();
-                            if !state.display {
-                                if let Err(err) = tx.send("showGUI".to_string()) {
-                                    eprintln!("Failed to send message: {}", err);
-                                    state.display = false;
-                                } else {
-                                    println!("Message sent successfully.");
-                                }
-                            } else {
-                                println!("GUI already active. Skipping message.");
-                            }
-                        }
-
-                        // Disattiva il detector e avvia il backup
-                        detector_running.store(false, Ordering::Relaxed);
-
-                        avvia_backup(
-                            Arc::clone(&shared_state),
-                            Arc::clone(&detector_running),
-                            tx.clone(),           // Aggiungi il trasmettitore
-                            Arc::clone(&rx_stop), // Passa il canale di stop al backup
-                        );
-
+                        confirm_backup(&shared_state, &detector_running, &tx, &rx_stop);
                         *waiting = false;
                     } else {
                         println!("Movimento non riconosciuto come linea orizzontale di conferma.");
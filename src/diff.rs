@@ -0,0 +1,223 @@
+use crate::manifest::{self, RunManifest};
+use crate::storage::StorageBackend;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// File presente in entrambi i backup ma con contenuto diverso
+#[derive(Serialize, Clone, Debug)]
+pub struct ModifiedFile {
+    pub path: String,
+    pub old_size: u64,
+    pub new_size: u64,
+}
+
+/// File con lo stesso contenuto salvato con un percorso diverso
+#[derive(Serialize, Clone, Debug)]
+pub struct RenamedFile {
+    pub from: String,
+    pub to: String,
+    pub size: u64,
+}
+
+/// Differenze tra due backup
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RunDiff {
+    pub old_run: String,
+    pub new_run: String,
+    pub added: Vec<(String, u64)>,   // percorso e dimensione
+    pub removed: Vec<(String, u64)>, // percorso e dimensione
+    pub modified: Vec<ModifiedFile>,
+    pub renamed: Vec<RenamedFile>,
+}
+
+impl RunDiff {
+    /// Variazione complessiva della dimensione tra i due backup
+    pub fn size_delta(&self) -> i64 {
+        let added: i64 = self.added.iter().map(|(_, size)| *size as i64).sum();
+        let removed: i64 = self.removed.iter().map(|(_, size)| *size as i64).sum();
+        let modified: i64 = self
+            .modified
+            .iter()
+            .map(|m| m.new_size as i64 - m.old_size as i64)
+            .sum();
+        added - removed + modified
+    }
+
+    /// Testo del confronto, usato dalla riga di comando
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![format!("Comparing backup {} -> {}", self.old_run, self.new_run)];
+        for (path, size) in &self.added {
+            lines.push(format!("A  {} (+{} bytes)", path, size));
+        }
+        for (path, size) in &self.removed {
+            lines.push(format!("D  {} (-{} bytes)", path, size));
+        }
+        for file in &self.modified {
+            lines.push(format!(
+                "M  {} ({:+} bytes)",
+                file.path,
+                file.new_size as i64 - file.old_size as i64
+            ));
+        }
+        for file in &self.renamed {
+            lines.push(format!("R  {} -> {}", file.from, file.to));
+        }
+        lines.push(format!(
+            "{} added, {} removed, {} modified, {} renamed, total size delta {:+} bytes",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len(),
+            self.renamed.len(),
+            self.size_delta()
+        ));
+        lines.join("\n")
+    }
+}
+
+/// Confronta due manifest
+pub fn diff_runs(old: &RunManifest, new: &RunManifest) -> RunDiff {
+    let old_files: HashMap<&str, _> = old.files.iter().map(|f| (f.path.as_str(), f)).collect();
+    let new_files: HashMap<&str, _> = new.files.iter().map(|f| (f.path.as_str(), f)).collect();
+
+    let mut diff = RunDiff {
+        old_run: old.run_id.clone(),
+        new_run: new.run_id.clone(),
+        ..Default::default()
+    };
+
+    let mut added = vec![];
+    for file in &new.files {
+        match old_files.get(file.path.as_str()) {
+            Some(previous) if previous.hash != file.hash => diff.modified.push(ModifiedFile {
+                path: file.path.clone(),
+                old_size: previous.size,
+                new_size: file.size,
+            }),
+            Some(_) => {}
+            None => added.push(file),
+        }
+    }
+
+    // Un file rimosso il cui contenuto ricompare con un altro percorso è considerato rinominato
+    let mut removed_by_hash: HashMap<&str, Vec<_>> = HashMap::new();
+    for file in &old.files {
+        if !new_files.contains_key(file.path.as_str()) {
+            removed_by_hash.entry(file.hash.as_str()).or_default().push(file);
+        }
+    }
+    for file in added {
+        match removed_by_hash.get_mut(file.hash.as_str()).and_then(|files| files.pop()) {
+            Some(previous) => diff.renamed.push(RenamedFile {
+                from: previous.path.clone(),
+                to: file.path.clone(),
+                size: file.size,
+            }),
+            None => diff.added.push((file.path.clone(), file.size)),
+        }
+    }
+    for files in removed_by_hash.into_values() {
+        diff.removed.extend(files.into_iter().map(|f| (f.path.clone(), f.size)));
+    }
+
+    diff.added.sort();
+    diff.removed.sort();
+    diff.modified.sort_by(|a, b| a.path.cmp(&b.path));
+    diff.renamed.sort_by(|a, b| a.to.cmp(&b.to));
+    diff
+}
+
+/// Confronta due backup salvati nella stessa destinazione. I backup parziali contengono solo
+/// i file cambiati: si confronta il contenuto completo della destinazione dopo ciascun backup.
+pub fn diff_stored_runs(
+    backend: &dyn StorageBackend,
+    old_run: &str,
+    new_run: &str,
+) -> Result<RunDiff, String> {
+    let old = manifest::snapshot(backend, old_run)
+        .map_err(|e| format!("Unable to read backup {}: {}", old_run, e))?;
+    let new = manifest::snapshot(backend, new_run)
+        .map_err(|e| format!("Unable to read backup {}: {}", new_run, e))?;
+    Ok(diff_runs(&old, &new))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::ManifestEntry;
+
+    fn entry(path: &str, size: u64, hash: &str) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            size,
+            modified: 0,
+            hash: hash.to_string(),
+        }
+    }
+
+    fn run(run_id: &str, files: Vec<ManifestEntry>) -> RunManifest {
+        RunManifest {
+            run_id: run_id.to_string(),
+            files,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_added_removed_and_modified_files() {
+        let old = run(
+            "1",
+            vec![entry("same.txt", 10, "a"), entry("edit.txt", 10, "b"), entry("gone.txt", 7, "c")],
+        );
+        let new = run(
+            "2",
+            vec![entry("same.txt", 10, "a"), entry("edit.txt", 25, "b2"), entry("new.txt", 3, "d")],
+        );
+        let diff = diff_runs(&old, &new);
+
+        assert_eq!(diff.added, vec![("new.txt".to_string(), 3)]);
+        assert_eq!(diff.removed, vec![("gone.txt".to_string(), 7)]);
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].path, "edit.txt");
+        assert!(diff.renamed.is_empty());
+        assert_eq!(diff.size_delta(), 3 - 7 + 15);
+    }
+
+    #[test]
+    fn moved_content_is_a_rename() {
+        let old = run("1", vec![entry("docs/report.pdf", 100, "r")]);
+        let new = run("2", vec![entry("archive/report-2023.pdf", 100, "r")]);
+        let diff = diff_runs(&old, &new);
+
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.renamed.len(), 1);
+        assert_eq!(diff.renamed[0].from, "docs/report.pdf");
+        assert_eq!(diff.renamed[0].to, "archive/report-2023.pdf");
+        assert_eq!(diff.size_delta(), 0);
+    }
+
+    #[test]
+    fn copies_of_an_existing_file_are_not_renames() {
+        // Il file originale è ancora presente: la nuova copia è un file aggiunto
+        let old = run("1", vec![entry("a.txt", 5, "x")]);
+        let new = run("2", vec![entry("a.txt", 5, "x"), entry("b.txt", 5, "x")]);
+        let diff = diff_runs(&old, &new);
+
+        assert_eq!(diff.added, vec![("b.txt".to_string(), 5)]);
+        assert!(diff.renamed.is_empty());
+    }
+
+    #[test]
+    fn identical_files_are_paired_once() {
+        // Due file identici rimossi e uno solo ricomparso: una rinomina e una rimozione
+        let old = run("1", vec![entry("one.txt", 4, "same"), entry("two.txt", 4, "same")]);
+        let new = run("2", vec![entry("moved.txt", 4, "same")]);
+        let diff = diff_runs(&old, &new);
+
+        assert_eq!(diff.renamed.len(), 1);
+        assert_eq!(diff.renamed[0].to, "moved.txt");
+        assert_eq!(diff.removed.len(), 1);
+        assert_ne!(diff.removed[0].0, diff.renamed[0].from);
+        assert!(diff.added.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::manifest::{self, ManifestEntry};
+use crate::storage::StorageBackend;
+use crate::utils::GuardConfig;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+// Formati già compressi: un'entropia alta per questi file è normale
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "zip", "gz", "7z", "rar", "xz", "bz2", "jpg", "jpeg", "png", "gif", "webp", "mp3", "mp4",
+    "mkv", "avi", "mov", "pdf", "docx", "xlsx", "pptx", "odt", "ods", "jar", "apk",
+];
+const ENTROPY_SAMPLE: usize = 64 * 1024;
+const HIGH_ENTROPY: f64 = 7.5; // bit per byte (il massimo è 8)
+
+/// Statistiche del confronto tra la sorgente e il backup precedente
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    pub previous_files: usize,
+    pub changed: usize,       // file del backup precedente modificati o spariti
+    pub high_entropy: usize,  // file modificati con contenuto apparentemente cifrato
+    pub new_extension: usize, // file con estensioni mai viste nel backup precedente
+    pub scanned: usize,
+}
+
+/// Entropia di Shannon dei primi byte del file
+fn sample_entropy(path: &Path) -> io::Result<f64> {
+    let mut buffer = vec![0u8; ENTROPY_SAMPLE];
+    let read = File::open(path)?.read(&mut buffer)?;
+    if read == 0 {
+        return Ok(0.0);
+    }
+    let mut counts = [0usize; 256];
+    for byte in &buffer[..read] {
+        counts[*byte as usize] += 1;
+    }
+    Ok(counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / read as f64;
+            -p * p.log2()
+        })
+        .sum())
+}
+
+fn extension_of(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next()?;
+    let (_, ext) = name.rsplit_once('.')?;
+    Some(ext.to_lowercase())
+}
+
+fn scan_folder(
+    root: &Path,
+    folder: &Path,
+    include: &dyn Fn(&Path) -> bool,
+    previous: &HashMap<&str, &ManifestEntry>,
+    known_extensions: &HashSet<String>,
+    seen: &mut HashSet<String>,
+    stats: &mut ScanStats,
+) -> io::Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan_folder(root, &path, include, previous, known_extensions, seen, stats)?;
+            continue;
+        }
+        if !path.is_file() || !include(&path) {
+            continue;
+        }
+        stats.scanned += 1;
+        let relative = manifest::relative_path(root, &path);
+        let metadata = path.metadata()?;
+
+        if let Some(ext) = extension_of(&relative) {
+            if !known_extensions.contains(&ext) {
+                stats.new_extension += 1;
+            }
+        }
+
+        if let Some(old) = previous.get(relative.as_str()) {
+            seen.insert(relative.clone());
+            let unchanged = old.size == metadata.len() && old.modified == manifest::modified_secs(&metadata);
+            if !unchanged {
+                stats.changed += 1;
+                let compressed = extension_of(&relative)
+                    .map(|ext| COMPRESSED_EXTENSIONS.contains(&ext.as_str()))
+                    .unwrap_or(false);
+                if !compressed && sample_entropy(&path).unwrap_or(0.0) >= HIGH_ENTROPY {
+                    stats.high_entropy += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Confronta la sorgente con l'ultimo backup completo della destinazione.
+/// Restituisce un messaggio di avviso se le modifiche superano le soglie configurate.
+pub fn check_mass_change(
+    source: &Path,
+    backend: &dyn StorageBackend,
+    include: &dyn Fn(&Path) -> bool,
+    config: &GuardConfig,
+) -> io::Result<Option<String>> {
+    // I backup parziali (protezione continua, spegnimento, batteria scarica) non spostano il
+    // riferimento: le loro modifiche si sommano finché un backup completo non le conferma, così
+    // tante piccole raffiche non sfuggono al controllo e non ne falsano il confronto
+    let previous = match manifest::last_full_run(backend)? {
+        Some(run) => run,
+        None => match manifest::list_runs(backend).last() {
+            // Solo backup parziali: si confronta con il contenuto complessivo della destinazione
+            Some(run_id) => manifest::snapshot(backend, run_id)?,
+            None => return Ok(None), // primo backup: niente con cui confrontare
+        },
+    };
+    if previous.files.len() < config.min_files {
+        return Ok(None);
+    }
+
+    let known_extensions: HashSet<String> = previous
+        .files
+        .iter()
+        .filter_map(|f| extension_of(&f.path))
+        .collect();
+    let previous_files: HashMap<&str, &ManifestEntry> =
+        previous.files.iter().map(|f| (f.path.as_str(), f)).collect();
+    let mut seen = HashSet::new();
+    let mut stats = ScanStats {
+        previous_files: previous_files.len(),
+        ..Default::default()
+    };
+    scan_folder(source, source, include, &previous_files, &known_extensions, &mut seen, &mut stats)?;
+    // Anche i file spariti dalla sorgente contano come modificati
+    stats.changed += previous_files.len() - seen.len();
+
+    let changed_ratio = stats.changed as f32 / stats.previous_files as f32;
+    let entropy_ratio = if stats.changed > 0 {
+        stats.high_entropy as f32 / stats.changed as f32
+    } else {
+        0.0
+    };
+    let extension_ratio = if stats.scanned > 0 {
+        stats.new_extension as f32 / stats.scanned as f32
+    } else {
+        0.0
+    };
+
+    let mut reasons = vec![];
+    if changed_ratio >= config.changed_ratio {
+        reasons.push(format!(
+            "{:.0}% of the files changed since backup {}",
+            changed_ratio * 100.0,
+            previous.run_id
+        ));
+    }
+    if stats.high_entropy > 0 && entropy_ratio >= config.entropy_ratio {
+        reasons.push(format!(
+            "{} changed file(s) look encrypted (high entropy)",
+            stats.high_entropy
+        ));
+    }
+    if stats.new_extension > 0 && extension_ratio >= config.new_extension_ratio {
+        reasons.push(format!(
+            "{} file(s) have extensions never seen before",
+            stats.new_extension
+        ));
+    }
+
+    if reasons.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(format!(
+            "Suspicious changes in the source folder: {}. Continuing may overwrite good copies with damaged files.",
+            reasons.join("; ")
+        )))
+    }
+}
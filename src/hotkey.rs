@@ -0,0 +1,153 @@
+use crate::utils::{load_config, HotkeyConfig};
+use rdev::Key;
+use std::time::{Duration, Instant};
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5); // ogni quanto si rilegge la sezione [hotkey]
+
+const LETTERS: [Key; 26] = [
+    Key::KeyA, Key::KeyB, Key::KeyC, Key::KeyD, Key::KeyE, Key::KeyF, Key::KeyG,
+    Key::KeyH, Key::KeyI, Key::KeyJ, Key::KeyK, Key::KeyL, Key::KeyM, Key::KeyN,
+    Key::KeyO, Key::KeyP, Key::KeyQ, Key::KeyR, Key::KeyS, Key::KeyT, Key::KeyU,
+    Key::KeyV, Key::KeyW, Key::KeyX, Key::KeyY, Key::KeyZ,
+];
+const DIGITS: [Key; 10] = [
+    Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4,
+    Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9,
+];
+const FUNCTION_KEYS: [Key; 12] = [
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+    Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+];
+
+/// Tasti accettati per un nome della configurazione (i modificatori valgono sia a sinistra sia a destra)
+fn parse_key(name: &str) -> Option<Vec<Key>> {
+    let name = name.trim().to_uppercase();
+    let keys = match name.as_str() {
+        "CTRL" | "CONTROL" => vec![Key::ControlLeft, Key::ControlRight],
+        "ALT" => vec![Key::Alt, Key::AltGr],
+        "SHIFT" => vec![Key::ShiftLeft, Key::ShiftRight],
+        "META" | "SUPER" | "WIN" => vec![Key::MetaLeft, Key::MetaRight],
+        "SPACE" => vec![Key::Space],
+        "ENTER" | "RETURN" => vec![Key::Return],
+        "TAB" => vec![Key::Tab],
+        "BACKSPACE" => vec![Key::Backspace],
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c @ 'A'..='Z'), None) => vec![LETTERS[(c as u8 - b'A') as usize]],
+                (Some(c @ '0'..='9'), None) => vec![DIGITS[(c as u8 - b'0') as usize]],
+                (Some('F'), Some(_)) => {
+                    let number: usize = name[1..].parse().ok()?;
+                    vec![*FUNCTION_KEYS.get(number.checked_sub(1)?)?]
+                }
+                _ => return None,
+            }
+        }
+    };
+    Some(keys)
+}
+
+/// Combinazione di tasti, ad esempio "Ctrl+Alt+Shift+B"
+#[derive(Debug, Clone)]
+pub struct Chord {
+    keys: Vec<Vec<Key>>, // per ogni tasto le varianti accettate
+}
+
+impl Chord {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let keys = text
+            .split('+')
+            .map(|name| parse_key(name).ok_or_else(|| format!("unknown key `{}` in `{}`", name.trim(), text)))
+            .collect::<Result<Vec<_>, String>>()?;
+        if keys.is_empty() {
+            return Err("empty key chord".to_string());
+        }
+        Ok(Chord { keys })
+    }
+
+    fn is_held(&self, pressed: &[Key]) -> bool {
+        self.keys
+            .iter()
+            .all(|variants| variants.iter().any(|key| pressed.contains(key)))
+    }
+}
+
+/// Segnala quando una combinazione resta premuta per il tempo richiesto. Il controllo avviene
+/// a ogni evento: mentre i tasti sono tenuti premuti la ripetizione automatica ne genera di continuo.
+pub struct ChordTracker {
+    chord: Chord,
+    hold: Duration,
+    held_since: Option<Instant>,
+    fired: bool, // già segnalata: va rilasciata prima di poterla usare di nuovo
+}
+
+impl ChordTracker {
+    pub fn new(chord: Chord, hold: Duration) -> Self {
+        ChordTracker {
+            chord,
+            hold,
+            held_since: None,
+            fired: false,
+        }
+    }
+
+    /// Aggiorna lo stato con i tasti premuti; restituisce true una sola volta per pressione
+    pub fn update(&mut self, pressed: &[Key]) -> bool {
+        if !self.chord.is_held(pressed) {
+            self.held_since = None;
+            self.fired = false;
+            return false;
+        }
+        let since = *self.held_since.get_or_insert_with(Instant::now);
+        if self.fired || since.elapsed() < self.hold {
+            return false;
+        }
+        self.fired = true;
+        true
+    }
+}
+
+/// Combinazioni di richiesta e conferma della sezione [hotkey]. Come inattività, programmazione e
+/// verifica, la configurazione viene riletta mentre il programma gira (al più ogni `RELOAD_INTERVAL`,
+/// all'arrivo di un evento) e le combinazioni vengono ricostruite solo se la sezione è cambiata.
+#[derive(Default)]
+pub struct Hotkeys {
+    loaded: Option<HotkeyConfig>,
+    checked_at: Option<Instant>,
+    pub trigger: Option<ChordTracker>,
+    pub confirm: Option<ChordTracker>,
+}
+
+impl Hotkeys {
+    pub fn refresh(&mut self) {
+        if self.checked_at.is_some_and(|at| at.elapsed() < RELOAD_INTERVAL) {
+            return;
+        }
+        self.checked_at = Some(Instant::now());
+
+        // Se il file non si legge (es. mentre viene salvato) restano le combinazioni già caricate
+        let config = match load_config() {
+            Some(config) => config.hotkey,
+            None if self.loaded.is_none() => HotkeyConfig::default(),
+            None => return,
+        };
+        if self.loaded.as_ref() == Some(&config) {
+            return;
+        }
+
+        let hold = Duration::from_secs_f32(config.hold_secs.max(0.0));
+        let tracker = |text: &str| match Chord::parse(text) {
+            Ok(chord) => Some(ChordTracker::new(chord, hold)),
+            Err(e) => {
+                eprintln!("Combinazione di tasti non valida: {}", e);
+                None
+            }
+        };
+        (self.trigger, self.confirm) = if config.enabled {
+            (tracker(&config.chord), tracker(&config.confirm_chord))
+        } else {
+            (None, None)
+        };
+        self.loaded = Some(config);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::cpu_gate::CpuGate;
+use crate::detector::BackupLauncher;
+use crate::power;
+use crate::queue;
+use crate::ui::BackupStatus;
+use crate::utils::load_config;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const GATE_KEY: &str = "idle backup"; // nome del job nel registro dei ritardi
+
+static LAST_INPUT: AtomicU64 = AtomicU64::new(0); // ultimo evento di mouse o tastiera (secondi dall'epoch)
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Chiamata dal listener di `detector::run` per ogni evento di input, anche durante un backup
+pub fn record_input() {
+    LAST_INPUT.store(now_secs(), Ordering::Relaxed);
+}
+
+/// Tempo trascorso dall'ultimo input dell'utente
+pub fn idle_for() -> Duration {
+    Duration::from_secs(now_secs().saturating_sub(LAST_INPUT.load(Ordering::Relaxed)))
+}
+
+/// Avvia il backup dopo `minutes` minuti senza input e, se l'utente torna mentre è in corso,
+/// lo mette in pausa (fino alla prossima assenza) o lo interrompe. Durante la pausa il detector
+/// resta attivo: il gesto annulla il backup in pausa e ne avvia uno nuovo.
+pub fn run_idle_trigger(launcher: BackupLauncher) {
+    record_input(); // l'avvio del programma conta come attività
+    let tx_stop = launcher.app.lock().unwrap().tx_stop.clone();
+    let mut fired = false; // backup già avviato in questo periodo di inattività
+    let mut running = false; // il backup in corso è stato avviato da questo trigger
+    let mut paused = false;
+    let mut cpu_gate = CpuGate::default();
+
+    loop {
+        thread::sleep(Duration::from_secs(5));
+        let Some(config) = load_config() else {
+            continue;
+        };
+        let settings = config.idle;
+        let idle = idle_for() >= Duration::from_secs(settings.minutes.max(1) * 60);
+        let user_back = idle_for() < Duration::from_secs(10);
+        if !idle {
+            fired = false;
+            cpu_gate.cancel(GATE_KEY);
+        }
+
+        if running {
+            if launcher.state.lock().unwrap().backup_status != BackupStatus::InProgress {
+                // Backup concluso (o fermato dalla GUI)
+                running = false;
+                paused = false;
+                continue;
+            }
+            if user_back && !paused {
+                if settings.on_return == "stop" {
+                    println!("L'utente è tornato: backup per inattività interrotto.");
+                    // Il thread del backup conclude con lo stato NotStarted, senza segnalare un errore
+                    launcher.state.lock().unwrap().backup_status = BackupStatus::Canceled;
+                    if let Err(e) = tx_stop.send("stop".to_string()) {
+                        eprintln!("Failed to send stop message to backup thread: {}", e);
+                    }
+                    running = false;
+                } else {
+                    println!("L'utente è tornato: backup per inattività in pausa.");
+                    paused = tx_stop.send("pause".to_string()).is_ok();
+                }
+            } else if paused && idle {
+                println!("Utente di nuovo inattivo: il backup riprende.");
+                // Come durante ogni backup, il detector torna disattivato
+                launcher.detector_running.store(false, Ordering::SeqCst);
+                paused = tx_stop.send("resume".to_string()).is_err();
+            } else if paused && launcher.state.lock().unwrap().backup_paused {
+                // Il backup è fermo: il gesto e la combinazione di tasti devono restare utilizzabili
+                launcher.release();
+            }
+            continue;
+        }
+
+        if !settings.enabled || !idle || fired || !launcher.is_idle() {
+            continue;
+        }
+        // A batteria si aspetta che torni la corrente (l'assenza continua, quindi si riprova)
+        if power::defer_reason().is_some() || !cpu_gate.ready(GATE_KEY) {
+            continue;
+        }
+        fired = true;
+        // Un backup per inattività non è urgente: se la destinazione manca si riprova alla prossima assenza
+        if !queue::destination_available() {
+            println!("Utente inattivo, ma la destinazione non è disponibile.");
+            continue;
+        }
+        running = launcher.start(&format!("no input for {} minutes", settings.minutes));
+    }
+}
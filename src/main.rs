@@ -3,12 +3,33 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
 mod analytics;
+mod battery_trigger;
+mod catalog;
+mod cli;
 mod confirm_sign;
+mod cpu_gate;
 mod detector;
+mod diff;
 mod first_sign;
+mod guard;
+mod hotkey;
+mod idle;
+mod manifest;
+mod mount_trigger;
+mod parity;
+mod peer;
+mod power;
+mod queue;
+mod replication;
+mod schedule;
+mod scrub;
+mod shutdown;
+mod storage;
 mod transfer;
 mod ui;
 mod utils;
+mod volumes;
+mod watcher;
 
 use crate::ui::{AppState, MyApp};
 use analytics::log_cpu_usage_to_csv;
@@ -69,6 +90,12 @@ fn set_working_directory_to_executable() {
 
 fn main() -> Result<(), eframe::Error> {
     set_working_directory_to_executable(); 
+
+    // Comandi da riga di comando (es. confronto tra due backup): non avviano l'applicazione
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        process::exit(cli::run(&args));
+    }
     // Imposta il panic hook per rimuovere il file di lock in caso di panico
     std::panic::set_hook(Box::new(|panic_info| {
         eprintln!("Panic occurred: {:?}", panic_info);
@@ -159,7 +186,14 @@ fn main() -> Result<(), eframe::Error> {
         thread::spawn(move || {
             for signal in signals.forever() {
                 match signal {
-                    signal::SIGINT | signal::SIGTERM => {
+                    signal::SIGTERM => {
+                        println!("Received termination signal. Cleaning up...");
+                        // Fine della sessione o spegnimento: ultimo backup rapido, se attivato
+                        shutdown::backup_before_exit();
+                        remove_lock_file();
+                        process::exit(0);
+                    }
+                    signal::SIGINT => {
                         println!("Received termination signal. Cleaning up...");
                         remove_lock_file();
                         process::exit(0);
@@ -187,6 +221,11 @@ fn main() -> Result<(), eframe::Error> {
         });
     }
 
+    // Trattiene lo spegnimento del sistema il tempo necessario al backup finale (se attivato)
+    thread::spawn(move || {
+        shutdown::run_shutdown_guard();
+    });
+
     // Avvia il logging della CPU in un thread separato
     thread::spawn(move || {
         log_cpu_usage_to_csv(); // Avvia la funzione di logging della CPU
@@ -226,6 +265,13 @@ fn main() -> Result<(), eframe::Error> {
     let monitor_tx = tx.clone(); 
     utils::monitor_lock_file(LOCK_FILE_PATH, monitor_state, monitor_tx);
 
+    // Avvia la verifica periodica dei backup salvati in un thread separato
+    let scrub_state = Arc::clone(&shared_state);
+    let scrub_tx = tx.clone();
+    thread::spawn(move || {
+        scrub::run_periodic_scrub(scrub_state, scrub_tx);
+    });
+
     // Load the application icon
     let icon_result = load_image_as_icon("images/icon.png");
 
@@ -261,6 +307,54 @@ fn main() -> Result<(), eframe::Error> {
         Arc::clone(&current_file),
     )));
 
+    // Condiviso tra il detector e gli altri modi di avviare il backup
+    let detector_running = Arc::new(AtomicBool::new(true));
+
+    // Per i backup avviati senza il gesto (collegamento del volume, coda, ...)
+    let launcher = detector::BackupLauncher {
+        app: Arc::clone(&my_app),
+        state: Arc::clone(&shared_state),
+        detector_running: Arc::clone(&detector_running),
+        tx: tx.clone(),
+        rx_stop: Arc::clone(&rx_stop),
+    };
+
+    // Avvia il backup automatico al collegamento del volume di destinazione
+    let mount_launcher = launcher.clone();
+    thread::spawn(move || {
+        mount_trigger::run_mount_watcher(mount_launcher);
+    });
+
+    // Recupera i backup rimandati quando la destinazione torna disponibile
+    let queue_launcher = launcher.clone();
+    thread::spawn(move || {
+        queue::run_queue_worker(queue_launcher);
+    });
+
+    // Esegue i backup e le repliche programmati
+    let schedule_launcher = launcher.clone();
+    thread::spawn(move || {
+        schedule::run_scheduler(schedule_launcher);
+    });
+
+    // Backup di emergenza quando la batteria sta per esaurirsi
+    let battery_launcher = launcher.clone();
+    thread::spawn(move || {
+        battery_trigger::run_battery_watch(battery_launcher);
+    });
+
+    // Avvia il backup quando l'utente non usa il computer da qualche minuto
+    let idle_launcher = launcher.clone();
+    thread::spawn(move || {
+        idle::run_idle_trigger(idle_launcher);
+    });
+
+    // Copia i file appena modificati nelle cartelle sorvegliate (protezione continua)
+    let watch_launcher = launcher.clone();
+    thread::spawn(move || {
+        watcher::run_watcher(watch_launcher);
+    });
+
     std::thread::spawn(move || {
         
         println!("Starting detector...");
@@ -269,7 +363,7 @@ fn main() -> Result<(), eframe::Error> {
             detector_tx,
             rx1,           // Passa rx1 per la comunicazione normale
             rx_stop_clone, // Passa rx_stop incapsulato per il controllo dello stop
-            Arc::new(AtomicBool::new(true)),
+            detector_running,
         );
     });
 
@@ -368,6 +462,9 @@ impl App for MyApp {
             ui::show_backup_window(ctx, self);
         }
 
+        // Avviso di corruzione trovato dalla verifica periodica
+        ui::render_scrub_alert(ctx, self);
+
         // Renderizza il modale di errore sopra l'overlay: viene messo qui perché rappresenta la conferma di chiusura dell'app
         if show_confirmation_modal {
             ui::render_modal_exit(ctx, self, frame); // Renderizza il modal
@@ -0,0 +1,208 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use crate::storage::{self, StorageBackend};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Cartella (dentro la destinazione) che contiene i metadati dei backup
+pub const METADATA_DIR: &str = ".group24";
+
+/// Singolo file registrato in un backup
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ManifestEntry {
+    pub path: String,  // percorso relativo alla cartella sorgente (separatore '/')
+    pub size: u64,     // dimensione in byte
+    pub modified: u64, // ultima modifica (secondi dall'epoch)
+    pub hash: String,  // SHA-256 in esadecimale
+}
+
+/// Elenco dei file salvati da una singola esecuzione del backup
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct RunManifest {
+    pub run_id: String,
+    pub source_folder: String,
+    pub files: Vec<ManifestEntry>,
+    // Solo i file cambiati (protezione continua, backup rapido allo spegnimento o a batteria scarica):
+    // i file non elencati restano quelli dei backup precedenti
+    #[serde(default)]
+    pub partial: bool,
+}
+
+static LAST_RUN_ID: Mutex<String> = Mutex::new(String::new()); // ultimo identificativo assegnato
+
+/// Genera l'identificativo di un nuovo backup a partire dall'ora corrente (al millisecondo).
+/// Backup avviati nello stesso millisecondo da thread diversi ricevono comunque identificativi
+/// distinti e crescenti, così nessun manifest sovrascrive l'altro.
+pub fn new_run_id() -> String {
+    let mut last = LAST_RUN_ID.lock().unwrap();
+    loop {
+        let run_id = Local::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+        if run_id > *last {
+            *last = run_id.clone();
+            return run_id;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Cartella dei manifest, relativa alla radice della destinazione
+pub const RUNS_DIR: &str = ".group24/runs";
+
+pub fn manifest_path(run_id: &str) -> String {
+    format!("{}/{}.toml", RUNS_DIR, run_id)
+}
+
+/// Cartella delle versioni sostituite, relativa alla radice della destinazione
+pub const OBJECTS_DIR: &str = ".group24/objects";
+
+/// Copia di una versione sostituita da un backup successivo, indicizzata per hash: la destinazione
+/// contiene solo l'ultima versione di ogni file, quelle precedenti restano qui per il ripristino
+pub fn object_path(hash: &str) -> String {
+    format!("{}/{}", OBJECTS_DIR, hash)
+}
+
+/// Salva il manifest di un backup in `<destinazione>/.group24/runs/<run_id>.toml`
+pub fn write_manifest(backend: &dyn StorageBackend, manifest: &RunManifest) -> io::Result<()> {
+    let content = toml::to_string(manifest)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+    storage::put_bytes(backend, &manifest_path(&manifest.run_id), content.as_bytes())?;
+    Ok(())
+}
+
+pub fn read_manifest(backend: &dyn StorageBackend, run_id: &str) -> io::Result<RunManifest> {
+    let content = storage::read_to_string(backend, &manifest_path(run_id))?;
+    toml::from_str(&content).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+/// Restituisce gli identificativi dei backup presenti nella destinazione, dal più vecchio al più recente
+pub fn list_runs(backend: &dyn StorageBackend) -> Vec<String> {
+    let mut runs = vec![];
+    match backend.list(RUNS_DIR) {
+        Ok(files) => {
+            for file in files {
+                if let Some(run_id) = file.path.rsplit('/').next().and_then(|name| name.strip_suffix(".toml")) {
+                    runs.push(run_id.to_string());
+                }
+            }
+        }
+        Err(e) => eprintln!("Impossibile elencare i backup di {}: {}", backend.describe(), e),
+    }
+    // Gli identificativi sono timestamp, quindi l'ordine alfabetico è anche quello cronologico
+    // (anche tra quelli vecchi, al secondo, e quelli nuovi, al millisecondo)
+    runs.sort();
+    runs
+}
+
+/// Restituisce, per ogni percorso, la versione registrata dal backup più recente
+pub fn latest_entries(backend: &dyn StorageBackend) -> io::Result<HashMap<String, ManifestEntry>> {
+    let mut latest = HashMap::new();
+    for run_id in list_runs(backend) {
+        let run = read_manifest(backend, &run_id)?;
+        for entry in run.files {
+            latest.insert(entry.path.clone(), entry);
+        }
+    }
+    Ok(latest)
+}
+
+/// Ultimo backup completo della destinazione (None se ci sono solo backup parziali o nessun backup)
+pub fn last_full_run(backend: &dyn StorageBackend) -> io::Result<Option<RunManifest>> {
+    for run_id in list_runs(backend).iter().rev() {
+        let run = read_manifest(backend, run_id)?;
+        if !run.partial {
+            return Ok(Some(run));
+        }
+    }
+    Ok(None)
+}
+
+/// Contenuto della destinazione subito dopo il backup `run_id`: l'ultimo backup completo fino a
+/// quel punto con i backup parziali successivi applicati sopra. Per un backup completo coincide
+/// con il suo manifest.
+pub fn snapshot(backend: &dyn StorageBackend, run_id: &str) -> io::Result<RunManifest> {
+    let runs = list_runs(backend);
+    let position = runs
+        .iter()
+        .position(|run| run == run_id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("backup {} not found", run_id)))?;
+
+    // Si risale fino al primo backup completo, poi si applicano i parziali in ordine
+    let mut partials = vec![];
+    let mut base = None;
+    for run in runs[..=position].iter().rev() {
+        let manifest = read_manifest(backend, run)?;
+        if manifest.partial {
+            partials.push(manifest);
+        } else {
+            base = Some(manifest);
+            break;
+        }
+    }
+    let mut files: HashMap<String, ManifestEntry> = base
+        .iter()
+        .flat_map(|manifest| manifest.files.iter())
+        .map(|entry| (entry.path.clone(), entry.clone()))
+        .collect();
+    let source_folder = base
+        .as_ref()
+        .or(partials.first())
+        .map(|manifest| manifest.source_folder.clone())
+        .unwrap_or_default();
+    for manifest in partials.into_iter().rev() {
+        for entry in manifest.files {
+            files.insert(entry.path.clone(), entry);
+        }
+    }
+
+    let mut files: Vec<ManifestEntry> = files.into_values().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(RunManifest {
+        run_id: run_id.to_string(),
+        source_folder,
+        files,
+        partial: false,
+    })
+}
+
+/// Converte un percorso relativo nel formato usato nei manifest
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Calcola lo SHA-256 di un file, leggendolo a blocchi
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    hash_reader(&mut File::open(path)?)
+}
+
+pub fn hash_reader(reader: &mut dyn Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn modified_secs(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
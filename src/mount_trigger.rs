@@ -0,0 +1,51 @@
+use crate::detector::BackupLauncher;
+use crate::utils::load_config;
+use crate::volumes;
+use std::thread;
+use std::time::Duration;
+
+/// Controlla periodicamente i volumi montati e avvia il backup quando viene collegato
+/// il volume registrato come destinazione, senza bisogno del gesto
+pub fn run_mount_watcher(launcher: BackupLauncher) {
+    // Un volume già collegato all'avvio non fa partire il backup: conta solo il collegamento
+    let mut connected = load_config()
+        .map(|config| volumes::find(&config.volume).is_some())
+        .unwrap_or(false);
+
+    loop {
+        thread::sleep(Duration::from_secs(2));
+
+        // La configurazione viene riletta ogni volta per applicare le modifiche fatte dalla GUI
+        let Some(config) = load_config() else {
+            continue;
+        };
+        let volume = if config.destination_type == "volume" {
+            volumes::find(&config.volume)
+        } else {
+            None
+        };
+        let just_connected = volume.is_some() && !connected;
+        connected = volume.is_some();
+        let Some(volume) = volume.filter(|_| just_connected && config.mount_trigger.enabled) else {
+            continue;
+        };
+
+        // Se c'è già un backup in corso (o in attesa di conferma) il collegamento viene ignorato
+        if !launcher.is_idle() {
+            println!("Volume {} collegato, ma un backup è già in corso.", volume.display_name());
+            continue;
+        }
+        {
+            let mut state = launcher.state.lock().unwrap();
+            state.eject_volume = if config.mount_trigger.eject_prompt {
+                Some(volume.clone())
+            } else {
+                None
+            };
+            state.eject_message = None;
+        }
+        if !launcher.start(&format!("volume {} connected", volume.display_name())) {
+            launcher.state.lock().unwrap().eject_volume = None;
+        }
+    }
+}
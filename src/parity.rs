@@ -0,0 +1,433 @@
+use crate::manifest::{self, ManifestEntry, METADATA_DIR};
+use crate::storage::{self, FileMeta, StorageBackend};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+// Formato del file di parità:
+// intestazione  -> MAGIC | blocchi dati (u8) | blocchi parità (u8) | dimensione blocco (u32) | dimensione file (u64)
+// per ogni stripe -> hash SHA-256 di tutti i blocchi (dati + parità) | blocchi di parità
+const MAGIC: &[u8; 8] = b"G24PAR01";
+const DATA_SHARDS: usize = 16;
+const MAX_SHARD_SIZE: usize = 64 * 1024;
+const HASH_SIZE: usize = 32;
+
+/// Risultato di una riparazione della destinazione
+#[derive(Debug, Default)]
+pub struct RepairReport {
+    pub checked: usize,
+    pub damaged: usize,
+    pub repaired: usize,
+    pub unrecoverable: Vec<String>,
+}
+
+impl RepairReport {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Checked files: {}\nDamaged files: {}\nRepaired files: {}",
+            self.checked, self.damaged, self.repaired
+        );
+        if !self.unrecoverable.is_empty() {
+            summary.push_str(&format!(
+                "\nUnrecoverable files: {}",
+                self.unrecoverable.join(", ")
+            ));
+        }
+        summary
+    }
+}
+
+/// I file di parità sono indicizzati per hash: file identici in backup diversi condividono la stessa parità.
+/// Il percorso è relativo alla radice della destinazione.
+pub fn parity_path(hash: &str) -> String {
+    format!("{}/parity/{}.par", METADATA_DIR, hash)
+}
+
+/// File temporaneo locale usato per generare o riparare dati prima di salvarli nella destinazione
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("group24_{}_{}", std::process::id(), name))
+}
+
+/// Numero di blocchi di parità per stripe data la ridondanza percentuale
+pub fn parity_shards(redundancy: u8) -> usize {
+    ((DATA_SHARDS * redundancy as usize + 99) / 100).max(1)
+}
+
+/// Percentuale di ogni stripe che può essere ricostruita a partire dalla parità
+pub fn recoverable_percentage(redundancy: u8) -> f32 {
+    parity_shards(redundancy) as f32 / DATA_SHARDS as f32 * 100.0
+}
+
+fn sha256(data: &[u8]) -> [u8; HASH_SIZE] {
+    let mut hash = [0u8; HASH_SIZE];
+    hash.copy_from_slice(&Sha256::digest(data));
+    hash
+}
+
+/// Legge fino a riempire il buffer o fino alla fine del file, restituendo i byte letti
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut total = 0;
+    while total < buffer.len() {
+        let read = reader.read(&mut buffer[total..])?;
+        if read == 0 {
+            break;
+        }
+        total += read;
+    }
+    Ok(total)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Genera il file di parità per `file` e ne restituisce la dimensione in byte
+pub fn write_parity(file: &Path, parity_file: &Path, redundancy: u8) -> io::Result<u64> {
+    let file_size = fs::metadata(file)?.len();
+    let parity_count = parity_shards(redundancy);
+    let shard_size = ((file_size as usize + DATA_SHARDS - 1) / DATA_SHARDS).clamp(1, MAX_SHARD_SIZE);
+    let stripe_size = shard_size * DATA_SHARDS;
+
+    let encoder = ReedSolomon::new(DATA_SHARDS, parity_count)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+
+    if let Some(parent) = parity_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut reader = BufReader::new(File::open(file)?);
+    let mut writer = BufWriter::new(File::create(parity_file)?);
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[DATA_SHARDS as u8, parity_count as u8])?;
+    writer.write_all(&(shard_size as u32).to_le_bytes())?;
+    writer.write_all(&file_size.to_le_bytes())?;
+
+    let mut stripe = vec![0u8; stripe_size];
+    let mut remaining = file_size;
+    while remaining > 0 {
+        stripe.iter_mut().for_each(|b| *b = 0);
+        let read = read_full(&mut reader, &mut stripe)?;
+        if read == 0 {
+            return Err(invalid_data("File changed while generating parity data."));
+        }
+        remaining = remaining.saturating_sub(read as u64);
+
+        let mut shards: Vec<Vec<u8>> = stripe.chunks(shard_size).map(|c| c.to_vec()).collect();
+        shards.extend((0..parity_count).map(|_| vec![0u8; shard_size]));
+        encoder
+            .encode(&mut shards)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+
+        for shard in &shards {
+            writer.write_all(&sha256(shard))?;
+        }
+        for shard in &shards[DATA_SHARDS..] {
+            writer.write_all(shard)?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(fs::metadata(parity_file)?.len())
+}
+
+/// Ricostruisce `file` usando il suo file di parità.
+/// Restituisce `Ok(true)` se almeno un blocco è stato riparato, `Ok(false)` se il file era integro.
+pub fn repair_file(file: &Path, parity_file: &Path) -> io::Result<bool> {
+    let mut parity = BufReader::new(File::open(parity_file)?);
+
+    let mut magic = [0u8; 8];
+    parity.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Invalid parity file."));
+    }
+    let mut counts = [0u8; 2];
+    parity.read_exact(&mut counts)?;
+    let (data_count, parity_count) = (counts[0] as usize, counts[1] as usize);
+    let mut shard_size = [0u8; 4];
+    parity.read_exact(&mut shard_size)?;
+    let shard_size = u32::from_le_bytes(shard_size) as usize;
+    let mut file_size = [0u8; 8];
+    parity.read_exact(&mut file_size)?;
+    let file_size = u64::from_le_bytes(file_size);
+
+    let decoder = ReedSolomon::new(data_count, parity_count)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+
+    // Un file mancante viene trattato come un file completamente danneggiato
+    let mut source: Box<dyn Read> = match File::open(file) {
+        Ok(f) => Box::new(BufReader::new(f)),
+        Err(_) => Box::new(io::empty()),
+    };
+    // Il suffisso va aggiunto al nome completo: `a.txt` e `a.doc` non devono condividere il file temporaneo
+    let mut repaired_name = file.file_name().unwrap_or_default().to_os_string();
+    repaired_name.push(".g24repair");
+    let repaired_path = file.with_file_name(repaired_name);
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(&repaired_path)?);
+
+    let stripe_size = shard_size * data_count;
+    let mut stripe = vec![0u8; stripe_size];
+    let mut remaining = file_size;
+    let mut repaired = false;
+
+    while remaining > 0 {
+        stripe.iter_mut().for_each(|b| *b = 0);
+        let read = read_full(&mut source, &mut stripe)?;
+        let stripe_len = (stripe_size as u64).min(remaining) as usize;
+
+        let mut hashes = vec![0u8; HASH_SIZE * (data_count + parity_count)];
+        parity.read_exact(&mut hashes)?;
+
+        let mut shards: Vec<Option<Vec<u8>>> = Vec::with_capacity(data_count + parity_count);
+        for (index, chunk) in stripe.chunks(shard_size).enumerate() {
+            let expected = &hashes[index * HASH_SIZE..(index + 1) * HASH_SIZE];
+            // I blocchi oltre la fine di un file troncato non sono affidabili anche se l'hash torna
+            let complete = read >= ((index + 1) * shard_size).min(stripe_len);
+            if complete && sha256(chunk) == expected {
+                shards.push(Some(chunk.to_vec()));
+            } else {
+                shards.push(None);
+            }
+        }
+        for index in 0..parity_count {
+            let mut shard = vec![0u8; shard_size];
+            parity.read_exact(&mut shard)?;
+            let position = data_count + index;
+            let expected = &hashes[position * HASH_SIZE..(position + 1) * HASH_SIZE];
+            shards.push(if sha256(&shard) == expected { Some(shard) } else { None });
+        }
+
+        if shards[..data_count].iter().any(|s| s.is_none()) {
+            decoder.reconstruct_data(&mut shards).map_err(|_| {
+                let _ = fs::remove_file(&repaired_path);
+                invalid_data("Too many damaged blocks: the file cannot be recovered.")
+            })?;
+            repaired = true;
+        }
+
+        let mut written = 0;
+        for shard in shards[..data_count].iter().flatten() {
+            let take = shard.len().min(stripe_len - written);
+            writer.write_all(&shard[..take])?;
+            written += take;
+            if written == stripe_len {
+                break;
+            }
+        }
+        remaining -= stripe_len as u64;
+    }
+    writer.flush()?;
+    drop(writer);
+    drop(source);
+
+    if repaired || fs::metadata(file).map(|m| m.len() != file_size).unwrap_or(true) {
+        fs::rename(&repaired_path, file)?;
+        Ok(true)
+    } else {
+        fs::remove_file(&repaired_path)?;
+        Ok(false)
+    }
+}
+
+/// Scarica un file della destinazione in un file temporaneo locale
+fn download(backend: &dyn StorageBackend, path: &str, local: &Path) -> io::Result<()> {
+    let mut reader = backend.get(path)?;
+    let mut file = File::create(local)?;
+    io::copy(&mut reader, &mut file)?;
+    Ok(())
+}
+
+/// Ripara un singolo file della destinazione lavorando su copie locali temporanee
+fn repair_stored(backend: &dyn StorageBackend, entry: &ManifestEntry) -> io::Result<()> {
+    let local_file = temp_path(&format!("{}.data", entry.hash));
+    let local_parity = temp_path(&format!("{}.par", entry.hash));
+
+    let result = (|| -> io::Result<()> {
+        download(backend, &parity_path(&entry.hash), &local_parity)?;
+        // Un file mancante nella destinazione viene ricostruito interamente dalla parità
+        if backend.stat(&entry.path)?.is_some() {
+            download(backend, &entry.path, &local_file)?;
+        }
+        repair_file(&local_file, &local_parity)?;
+        if manifest::hash_file(&local_file)? != entry.hash {
+            return Err(invalid_data("Repaired file does not match the recorded hash."));
+        }
+        let meta = FileMeta {
+            size: entry.size,
+            modified: entry.modified,
+            hash: Some(entry.hash.clone()),
+        };
+        backend.put(&entry.path, &mut File::open(&local_file)?, &meta)?;
+        Ok(())
+    })();
+
+    let _ = fs::remove_file(&local_file);
+    let _ = fs::remove_file(&local_parity);
+    result
+}
+
+/// Verifica tutti i file registrati nei manifest della destinazione e ripara quelli danneggiati
+pub fn repair_destination(backend: &dyn StorageBackend) -> io::Result<RepairReport> {
+    // Per ogni percorso conta solo la versione salvata dal backup più recente
+    let latest = manifest::latest_entries(backend)?;
+
+    let mut report = RepairReport::default();
+    for (path, entry) in latest {
+        report.checked += 1;
+        if storage::hash_stored(backend, &path).ok().as_deref() == Some(entry.hash.as_str()) {
+            continue;
+        }
+
+        report.damaged += 1;
+        if backend.stat(&parity_path(&entry.hash))?.is_none() {
+            report.unrecoverable.push(path);
+            continue;
+        }
+        match repair_stored(backend, &entry) {
+            Ok(()) => {
+                println!("File riparato: {}", path);
+                report.repaired += 1;
+            }
+            Err(e) => {
+                eprintln!("Impossibile riparare {}: {}", path, e);
+                report.unrecoverable.push(path);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Contenuto pseudo-casuale ripetibile, lungo abbastanza da occupare più stripe
+    fn sample_data(len: usize) -> Vec<u8> {
+        let mut state: u32 = 0x2545_f491;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// File di prova e relativa parità, cancellati alla fine del test
+    struct Fixture {
+        file: PathBuf,
+        parity: PathBuf,
+        data: Vec<u8>,
+    }
+
+    impl Fixture {
+        fn new(name: &str, len: usize, redundancy: u8) -> Self {
+            let data = sample_data(len);
+            let file = temp_path(&format!("test_{}.data", name));
+            let parity = temp_path(&format!("test_{}.par", name));
+            fs::write(&file, &data).unwrap();
+            write_parity(&file, &parity, redundancy).unwrap();
+            Fixture { file, parity, data }
+        }
+
+        fn shard_size(&self) -> usize {
+            self.data.len().div_ceil(DATA_SHARDS).clamp(1, MAX_SHARD_SIZE)
+        }
+
+        // Altera un byte all'interno del blocco `shard` della prima stripe
+        fn corrupt_shard(&self, shard: usize) {
+            let mut stored = fs::read(&self.file).unwrap();
+            stored[shard * self.shard_size() + 7] ^= 0xff;
+            fs::write(&self.file, stored).unwrap();
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.file);
+            let _ = fs::remove_file(&self.parity);
+        }
+    }
+
+    #[test]
+    fn parity_shards_round_up() {
+        assert_eq!(parity_shards(0), 1);
+        assert_eq!(parity_shards(10), 2);
+        assert_eq!(parity_shards(25), 4);
+        assert_eq!(parity_shards(100), 16);
+        assert_eq!(recoverable_percentage(25), 25.0);
+    }
+
+    #[test]
+    fn intact_file_is_left_alone() {
+        let fixture = Fixture::new("intact", 100_000, 25);
+        assert!(!repair_file(&fixture.file, &fixture.parity).unwrap());
+        assert_eq!(fs::read(&fixture.file).unwrap(), fixture.data);
+    }
+
+    #[test]
+    fn repairs_corrupted_shards() {
+        let fixture = Fixture::new("corrupted", 100_000, 25);
+        for shard in [0, 5, 9, 15] {
+            fixture.corrupt_shard(shard);
+        }
+        assert!(repair_file(&fixture.file, &fixture.parity).unwrap());
+        assert_eq!(fs::read(&fixture.file).unwrap(), fixture.data);
+    }
+
+    #[test]
+    fn repairs_damage_in_every_stripe() {
+        // Oltre `MAX_SHARD_SIZE * DATA_SHARDS` byte il file occupa più stripe
+        let fixture = Fixture::new("stripes", 2 * MAX_SHARD_SIZE * DATA_SHARDS + 1234, 10);
+        let stripe_size = fixture.shard_size() * DATA_SHARDS;
+        let mut stored = fs::read(&fixture.file).unwrap();
+        for stripe in 0..3 {
+            stored[stripe * stripe_size + 3] ^= 0x01;
+        }
+        fs::write(&fixture.file, stored).unwrap();
+
+        assert!(repair_file(&fixture.file, &fixture.parity).unwrap());
+        assert_eq!(fs::read(&fixture.file).unwrap(), fixture.data);
+    }
+
+    #[test]
+    fn repairs_truncated_file() {
+        let fixture = Fixture::new("truncated", 50_000, 25);
+        fs::write(&fixture.file, &fixture.data[..fixture.data.len() - 100]).unwrap();
+        assert!(repair_file(&fixture.file, &fixture.parity).unwrap());
+        assert_eq!(fs::read(&fixture.file).unwrap(), fixture.data);
+    }
+
+    #[test]
+    fn too_many_damaged_shards_fail_without_touching_the_file() {
+        // Con ridondanza 10% ogni stripe ha 2 blocchi di parità: 3 blocchi danneggiati sono troppi
+        let fixture = Fixture::new("unrecoverable", 100_000, 10);
+        for shard in [1, 2, 3] {
+            fixture.corrupt_shard(shard);
+        }
+        let damaged = fs::read(&fixture.file).unwrap();
+
+        let error = repair_file(&fixture.file, &fixture.parity).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read(&fixture.file).unwrap(), damaged);
+        let mut repaired_name = fixture.file.file_name().unwrap().to_os_string();
+        repaired_name.push(".g24repair");
+        assert!(!fixture.file.with_file_name(repaired_name).exists());
+    }
+
+    #[test]
+    fn rejects_foreign_parity_file() {
+        let fixture = Fixture::new("magic", 1_000, 25);
+        let mut parity = fs::read(&fixture.parity).unwrap();
+        parity[0] = b'X';
+        fs::write(&fixture.parity, parity).unwrap();
+        let error = repair_file(&fixture.file, &fixture.parity).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,740 @@
+use crate::storage::{FileMeta, LocalStorage, StorageBackend};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MAGIC: &[u8; 8] = b"G24PEER2"; // identifica il protocollo e la sua versione
+const NONCE_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const KDF_ROUNDS: u32 = 600_000; // iterazioni di PBKDF2 per ogni passphrase provata
+const SALT_FILE: &str = ".group24/peer_salt"; // sale del ricevitore, relativo alla cartella di ricezione
+pub const CHUNK_SIZE: usize = 256 * 1024; // dati trasportati da un singolo messaggio DATA
+const MAX_FRAME: usize = CHUNK_SIZE + 4096;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const IO_TIMEOUT: Duration = Duration::from_secs(600); // il ricevitore può impiegare tempo a verificare un file grande
+
+// Richieste del mittente
+pub const OP_STAT: u8 = 1;
+pub const OP_LIST: u8 = 2;
+pub const OP_GET: u8 = 3;
+pub const OP_PUT: u8 = 4;
+pub const OP_DELETE: u8 = 5;
+pub const OP_RENAME: u8 = 6;
+// Messaggi comuni e risposte
+pub const OP_DATA: u8 = 10;
+pub const OP_END: u8 = 11;
+pub const OP_OK: u8 = 12;
+pub const OP_ERROR: u8 = 13;
+pub const OP_ITEM: u8 = 14;
+pub const OP_NONE: u8 = 15;
+pub const OP_OFFSET: u8 = 16; // byte già ricevuti in un tentativo precedente (ripresa)
+pub const OP_ACK: u8 = 17; // conferma che il file è stato salvato e verificato
+
+/// Messaggio in chiaro: un codice seguito da campi (interi a 64 bit o stringhe precedute dalla lunghezza)
+pub struct Message {
+    pub kind: u8,
+    body: Vec<u8>,
+    pos: usize, // posizione di lettura dei campi
+}
+
+impl Message {
+    pub fn new(kind: u8) -> Self {
+        Message {
+            kind,
+            body: vec![],
+            pos: 0,
+        }
+    }
+
+    /// Messaggio DATA con un blocco di contenuto del file
+    pub fn data(bytes: &[u8]) -> Self {
+        Message {
+            kind: OP_DATA,
+            body: bytes.to_vec(),
+            pos: 0,
+        }
+    }
+
+    /// Messaggio ERROR: il tipo di errore viene conservato per distinguere ad esempio i file mancanti
+    pub fn error(error: &io::Error) -> Self {
+        let not_found = u64::from(error.kind() == io::ErrorKind::NotFound);
+        Message::new(OP_ERROR).with_u64(not_found).with_str(&error.to_string())
+    }
+
+    pub fn with_u64(mut self, value: u64) -> Self {
+        self.body.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn with_str(self, value: &str) -> Self {
+        let mut message = self.with_u64(value.len() as u64);
+        message.body.extend_from_slice(value.as_bytes());
+        message
+    }
+
+    pub fn take_u64(&mut self) -> io::Result<u64> {
+        let bytes = self
+            .body
+            .get(self.pos..self.pos + 8)
+            .ok_or_else(|| invalid("truncated message"))?;
+        self.pos += 8;
+        Ok(u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn take_str(&mut self) -> io::Result<String> {
+        let length = self.take_u64()? as usize;
+        let bytes = self
+            .body
+            .get(self.pos..self.pos.saturating_add(length))
+            .ok_or_else(|| invalid("truncated message"))?;
+        self.pos += length;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("invalid text in message"))
+    }
+
+    /// Contenuto di un messaggio DATA
+    pub fn into_data(self) -> Vec<u8> {
+        self.body
+    }
+
+    /// Restituisce il messaggio se è del tipo atteso, altrimenti l'errore inviato dall'altro lato
+    pub fn expect(mut self, kind: u8) -> io::Result<Self> {
+        if self.kind == kind {
+            return Ok(self);
+        }
+        if self.kind == OP_ERROR {
+            let error_kind = if self.take_u64()? == 1 {
+                io::ErrorKind::NotFound
+            } else {
+                io::ErrorKind::Other
+            };
+            return Err(io::Error::new(error_kind, self.take_str()?));
+        }
+        Err(invalid(&format!("unexpected message {} (expected {})", self.kind, kind)))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Ricava la chiave di 32 byte dalla passphrase con PBKDF2 e il sale del ricevitore: chi intercetta
+/// l'handshake deve ripetere tutte le iterazioni per ogni passphrase che vuole provare
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut key);
+    key
+}
+
+/// Chiave condivisa lato mittente. Il sale arriva dal ricevitore durante l'handshake; la chiave
+/// ricavata resta in memoria, così le connessioni successive non ripetono il calcolo.
+pub struct PeerKey {
+    passphrase: String,
+    derived: Mutex<Option<([u8; SALT_LEN], [u8; 32])>>, // ultimo sale ricevuto e chiave corrispondente
+}
+
+impl PeerKey {
+    pub fn new(passphrase: &str) -> Self {
+        PeerKey {
+            passphrase: passphrase.to_string(),
+            derived: Mutex::new(None),
+        }
+    }
+
+    fn for_salt(&self, salt: &[u8; SALT_LEN]) -> [u8; 32] {
+        let mut derived = self.derived.lock().unwrap();
+        if let Some((known, key)) = *derived {
+            if known == *salt {
+                return key;
+            }
+        }
+        let key = derive_key(&self.passphrase, salt);
+        *derived = Some((*salt, key));
+        key
+    }
+}
+
+/// Sale del ricevitore: generato al primo avvio e conservato nella cartella di ricezione
+fn receiver_salt(folder: &Path) -> io::Result<[u8; SALT_LEN]> {
+    let path = folder.join(SALT_FILE);
+    if let Ok(bytes) = fs::read(&path) {
+        if let Ok(salt) = <[u8; SALT_LEN]>::try_from(bytes) {
+            return Ok(salt);
+        }
+    }
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, salt)?;
+    Ok(salt)
+}
+
+/// HMAC della chiave condivisa su etichetta e nonce delle due parti: serve sia per le prove
+/// di conoscenza della chiave sia per ricavare le chiavi di sessione
+fn keyed_hash(psk: &[u8; 32], label: &[u8], client_nonce: &[u8], server_nonce: &[u8]) -> HmacSha256 {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(psk).expect("HMAC accetta chiavi di qualsiasi lunghezza");
+    mac.update(label);
+    mac.update(client_nonce);
+    mac.update(server_nonce);
+    mac
+}
+
+fn session_cipher(psk: &[u8; 32], label: &[u8], client_nonce: &[u8], server_nonce: &[u8]) -> ChaCha20Poly1305 {
+    let key = keyed_hash(psk, label, client_nonce, server_nonce).finalize().into_bytes();
+    ChaCha20Poly1305::new(Key::from_slice(&key))
+}
+
+/// Nonce del cifrario: ogni direzione ha la sua chiave e un contatore che non si ripete
+fn frame_nonce(counter: u64) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+/// Connessione TCP autenticata in entrambe le direzioni con la chiave condivisa e cifrata
+/// (ChaCha20-Poly1305, che rileva anche messaggi alterati o riordinati)
+pub struct SecureChannel {
+    stream: TcpStream,
+    send_cipher: ChaCha20Poly1305,
+    receive_cipher: ChaCha20Poly1305,
+    send_counter: u64,
+    receive_counter: u64,
+}
+
+impl SecureChannel {
+    /// Lato mittente: si collega al ricevitore e verifica che conosca la chiave prima di provarla a sua volta
+    pub fn connect(host: &str, port: u16, key: &PeerKey) -> io::Result<Self> {
+        let address = (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "host not found"))?;
+        let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        let mut client_nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut client_nonce);
+        stream.write_all(MAGIC)?;
+        stream.write_all(&client_nonce)?;
+
+        let mut salt = [0u8; SALT_LEN];
+        let mut server_nonce = [0u8; NONCE_LEN];
+        let mut server_proof = [0u8; 32];
+        stream.read_exact(&mut salt)?;
+        stream.read_exact(&mut server_nonce)?;
+        stream.read_exact(&mut server_proof)?;
+        let psk = key.for_salt(&salt);
+        keyed_hash(&psk, b"server", &client_nonce, &server_nonce)
+            .verify_slice(&server_proof)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "the receiver does not know the pre-shared key",
+                )
+            })?;
+        let client_proof = keyed_hash(&psk, b"client", &client_nonce, &server_nonce)
+            .finalize()
+            .into_bytes();
+        stream.write_all(&client_proof)?;
+
+        let mut channel = SecureChannel {
+            stream,
+            send_cipher: session_cipher(&psk, b"client->server", &client_nonce, &server_nonce),
+            receive_cipher: session_cipher(&psk, b"server->client", &client_nonce, &server_nonce),
+            send_counter: 0,
+            receive_counter: 0,
+        };
+        // Il primo messaggio cifrato conferma che il ricevitore ha accettato la prova
+        channel.receive().and_then(|reply| reply.expect(OP_OK)).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the receiver rejected the pre-shared key",
+            )
+        })?;
+        Ok(channel)
+    }
+
+    /// Lato ricevitore: invia il sale, risponde alla sfida del mittente e verifica la sua prova.
+    /// `psk` è la chiave già ricavata dalla passphrase con quel sale.
+    pub fn accept(mut stream: TcpStream, salt: &[u8; SALT_LEN], psk: &[u8; 32]) -> io::Result<Self> {
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+
+        let mut magic = [0u8; 8];
+        stream.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a backup sender"));
+        }
+        let mut client_nonce = [0u8; NONCE_LEN];
+        stream.read_exact(&mut client_nonce)?;
+        let mut server_nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut server_nonce);
+        let server_proof = keyed_hash(psk, b"server", &client_nonce, &server_nonce)
+            .finalize()
+            .into_bytes();
+        stream.write_all(salt)?;
+        stream.write_all(&server_nonce)?;
+        stream.write_all(&server_proof)?;
+
+        let mut client_proof = [0u8; 32];
+        stream.read_exact(&mut client_proof)?;
+        keyed_hash(psk, b"client", &client_nonce, &server_nonce)
+            .verify_slice(&client_proof)
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "the sender does not know the pre-shared key",
+                )
+            })?;
+
+        let mut channel = SecureChannel {
+            stream,
+            send_cipher: session_cipher(psk, b"server->client", &client_nonce, &server_nonce),
+            receive_cipher: session_cipher(psk, b"client->server", &client_nonce, &server_nonce),
+            send_counter: 0,
+            receive_counter: 0,
+        };
+        channel.send(&Message::new(OP_OK))?;
+        Ok(channel)
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let mut plain = Vec::with_capacity(message.body.len() + 1);
+        plain.push(message.kind);
+        plain.extend_from_slice(&message.body);
+        let sealed = self
+            .send_cipher
+            .encrypt(Nonce::from_slice(&frame_nonce(self.send_counter)), plain.as_slice())
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "encryption failed"))?;
+        self.send_counter += 1;
+
+        // Lunghezza e contenuto in un'unica scrittura
+        let mut frame = Vec::with_capacity(sealed.len() + 4);
+        frame.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&sealed);
+        self.stream.write_all(&frame)
+    }
+
+    pub fn receive(&mut self) -> io::Result<Message> {
+        let mut length = [0u8; 4];
+        self.stream.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > MAX_FRAME {
+            return Err(invalid("message too large"));
+        }
+        let mut sealed = vec![0u8; length];
+        self.stream.read_exact(&mut sealed)?;
+        let mut plain = self
+            .receive_cipher
+            .decrypt(Nonce::from_slice(&frame_nonce(self.receive_counter)), sealed.as_slice())
+            .map_err(|_| invalid("message altered in transit"))?;
+        self.receive_counter += 1;
+        if plain.is_empty() {
+            return Err(invalid("empty message"));
+        }
+        let body = plain.split_off(1);
+        Ok(Message {
+            kind: plain[0],
+            body,
+            pos: 0,
+        })
+    }
+}
+
+/// Modalità ricevitore: accetta i backup inviati da un altro PC e li salva in `folder`
+pub fn run_receiver(folder: &Path, port: u16, key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("A pre-shared key is required: set `key` in the [peer] section of config_build.toml.".to_string());
+    }
+    let storage = Arc::new(LocalStorage::open(folder)?);
+    // La chiave viene ricavata una sola volta: PBKDF2 è volutamente lento
+    let salt = receiver_salt(folder).map_err(|e| format!("Unable to create the receiver salt: {}", e))?;
+    let psk = derive_key(key, &salt);
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .map_err(|e| format!("Unable to listen on port {}: {}", port, e))?;
+    println!("In attesa di backup sulla porta {} (cartella {})...", port, folder.display());
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Connessione non accettata: {}", e);
+                continue;
+            }
+        };
+        let storage = Arc::clone(&storage);
+        let incoming = folder.join(".group24").join("incoming");
+        thread::spawn(move || {
+            let sender = stream
+                .peer_addr()
+                .map(|address| address.to_string())
+                .unwrap_or_default();
+            match SecureChannel::accept(stream, &salt, &psk) {
+                Ok(channel) => {
+                    println!("Mittente {} autenticato.", sender);
+                    match serve(channel, storage.as_ref(), &incoming) {
+                        Ok(()) => println!("Mittente {} disconnesso.", sender),
+                        Err(e) => eprintln!("Connessione con {} interrotta: {}", sender, e),
+                    }
+                }
+                Err(e) => eprintln!("Connessione da {} rifiutata: {}", sender, e),
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Rifiuta i percorsi che uscirebbero dalla cartella di ricezione
+fn checked_path(path: String) -> io::Result<String> {
+    let unsafe_component = path
+        .split(['/', '\\'])
+        .any(|component| component == ".." || component.contains(':'));
+    if unsafe_component || path.starts_with('/') || path.starts_with('\\') {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("path `{}` is outside the destination", path),
+        ));
+    }
+    Ok(path)
+}
+
+/// Esegue le richieste di un mittente finché non chiude la connessione
+fn serve(mut channel: SecureChannel, storage: &LocalStorage, incoming: &Path) -> io::Result<()> {
+    loop {
+        let mut request = match channel.receive() {
+            Ok(request) => request,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match request.kind {
+            OP_STAT => {
+                let reply = match checked_path(request.take_str()?).and_then(|path| storage.stat(&path)) {
+                    Ok(Some(info)) => Message::new(OP_ITEM)
+                        .with_str(&info.path)
+                        .with_u64(info.size)
+                        .with_u64(info.modified),
+                    Ok(None) => Message::new(OP_NONE),
+                    Err(e) => Message::error(&e),
+                };
+                channel.send(&reply)?;
+            }
+            OP_LIST => match checked_path(request.take_str()?).and_then(|prefix| storage.list(&prefix)) {
+                Ok(files) => {
+                    // Gli incompleti della ripresa e il sale non fanno parte del backup
+                    let internal = |path: &str| path.starts_with(".group24/incoming/") || path == SALT_FILE;
+                    for info in files.iter().filter(|info| !internal(&info.path)) {
+                        channel.send(
+                            &Message::new(OP_ITEM)
+                                .with_str(&info.path)
+                                .with_u64(info.size)
+                                .with_u64(info.modified),
+                        )?;
+                    }
+                    channel.send(&Message::new(OP_END))?;
+                }
+                Err(e) => channel.send(&Message::error(&e))?,
+            },
+            OP_GET => match checked_path(request.take_str()?).and_then(|path| storage.get(&path)) {
+                Ok(mut reader) => {
+                    let mut buffer = vec![0u8; CHUNK_SIZE];
+                    loop {
+                        match reader.read(&mut buffer) {
+                            Ok(0) => {
+                                channel.send(&Message::new(OP_END))?;
+                                break;
+                            }
+                            Ok(read) => channel.send(&Message::data(&buffer[..read]))?,
+                            Err(e) => {
+                                channel.send(&Message::error(&e))?;
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => channel.send(&Message::error(&e))?,
+            },
+            OP_PUT => receive_file(&mut channel, storage, incoming, request)?,
+            OP_DELETE => {
+                let result = checked_path(request.take_str()?).and_then(|path| storage.delete(&path));
+                channel.send(&match result {
+                    Ok(()) => Message::new(OP_OK),
+                    Err(e) => Message::error(&e),
+                })?;
+            }
+            OP_RENAME => {
+                let from = request.take_str()?;
+                let to = request.take_str()?;
+                let result = match (checked_path(from), checked_path(to)) {
+                    (Ok(from), Ok(to)) => storage.rename(&from, &to),
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                };
+                channel.send(&match result {
+                    Ok(()) => Message::new(OP_OK),
+                    Err(e) => Message::error(&e),
+                })?;
+            }
+            other => return Err(invalid(&format!("unknown request {}", other))),
+        }
+    }
+}
+
+/// Riceve un file: i dati arrivano in un file parziale che sopravvive a una connessione interrotta,
+/// così il tentativo successivo riprende dai byte già ricevuti. Il file viene confermato (ACK)
+/// solo dopo averne verificato l'hash
+fn receive_file(
+    channel: &mut SecureChannel,
+    storage: &LocalStorage,
+    incoming: &Path,
+    mut request: Message,
+) -> io::Result<()> {
+    let path = request.take_str()?;
+    let meta = FileMeta {
+        size: request.take_u64()?,
+        modified: request.take_u64()?,
+        hash: Some(request.take_str()?).filter(|hash| !hash.is_empty()),
+    };
+    let path = match checked_path(path) {
+        Ok(path) => path,
+        Err(e) => return channel.send(&Message::error(&e)),
+    };
+
+    // Senza hash non si può sapere se i dati parziali appartengono alla stessa versione del file
+    let part_name = match &meta.hash {
+        Some(hash) => hash.clone(),
+        None => format!("{:x}", Sha256::digest(path.as_bytes())),
+    };
+    let part_path: PathBuf = incoming.join(format!("{}.part", part_name));
+    let mut part = match fs::create_dir_all(incoming).and_then(|_| {
+        OpenOptions::new().create(true).read(true).write(true).open(&part_path)
+    }) {
+        Ok(part) => part,
+        Err(e) => return channel.send(&Message::error(&e)),
+    };
+    let mut offset = part.metadata()?.len();
+    if meta.hash.is_none() || offset > meta.size {
+        offset = 0;
+    }
+    part.set_len(offset)?;
+    part.seek(SeekFrom::Start(offset))?;
+    channel.send(&Message::new(OP_OFFSET).with_u64(offset))?;
+
+    // Un errore di rete interrompe qui la ricezione lasciando il file parziale per la ripresa
+    loop {
+        let message = channel.receive()?;
+        match message.kind {
+            OP_DATA => part.write_all(&message.into_data())?,
+            OP_END => break,
+            _ => return Err(message.expect(OP_DATA).err().unwrap_or_else(|| invalid("unexpected message"))),
+        }
+    }
+    part.sync_all()?;
+    drop(part);
+
+    let result = if fs::metadata(&part_path)?.len() != meta.size {
+        Err(invalid("received size does not match"))
+    } else {
+        File::open(&part_path).and_then(|mut data| storage.put(&path, &mut data, &meta))
+    };
+    // I dati parziali non servono più: se la verifica è fallita vanno comunque ritrasmessi da capo
+    let _ = fs::remove_file(&part_path);
+    match result {
+        Ok(written) => {
+            println!("Ricevuto {} ({} byte)", path, written);
+            channel.send(&Message::new(OP_ACK).with_u64(written))
+        }
+        Err(e) => {
+            eprintln!("Ricezione di {} non riuscita: {}", path, e);
+            channel.send(&Message::error(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SALT: [u8; SALT_LEN] = [3; SALT_LEN];
+
+    // Chiave già ricavata per TEST_SALT: i test non ripetono le iterazioni di PBKDF2
+    fn test_key(psk: [u8; 32]) -> PeerKey {
+        PeerKey {
+            passphrase: String::new(),
+            derived: Mutex::new(Some((TEST_SALT, psk))),
+        }
+    }
+
+    /// Ricevitore su una porta locale libera: `serve` riceve il risultato dell'handshake
+    fn spawn_receiver<T: Send + 'static>(
+        psk: [u8; 32],
+        serve: impl FnOnce(io::Result<SecureChannel>) -> T + Send + 'static,
+    ) -> (u16, thread::JoinHandle<T>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(SecureChannel::accept(stream, &TEST_SALT, &psk))
+        });
+        (port, handle)
+    }
+
+    fn stream_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    // Canale con chiavi di sessione fisse, senza handshake
+    fn channel(stream: TcpStream, send: &[u8], receive: &[u8]) -> SecureChannel {
+        let (psk, client_nonce, server_nonce) = ([9u8; 32], [1u8; NONCE_LEN], [2u8; NONCE_LEN]);
+        SecureChannel {
+            stream,
+            send_cipher: session_cipher(&psk, send, &client_nonce, &server_nonce),
+            receive_cipher: session_cipher(&psk, receive, &client_nonce, &server_nonce),
+            send_counter: 0,
+            receive_counter: 0,
+        }
+    }
+
+    /// Frame cifrati dal mittente, così come viaggiano sulla rete (lunghezza compresa)
+    fn sealed_frames(messages: &[Message]) -> Vec<Vec<u8>> {
+        let (client, mut wire) = stream_pair();
+        let mut sender = channel(client, b"client->server", b"server->client");
+        messages
+            .iter()
+            .map(|message| {
+                sender.send(message).unwrap();
+                let mut length = [0u8; 4];
+                wire.read_exact(&mut length).unwrap();
+                let mut frame = length.to_vec();
+                frame.resize(4 + u32::from_be_bytes(length) as usize, 0);
+                wire.read_exact(&mut frame[4..]).unwrap();
+                frame
+            })
+            .collect()
+    }
+
+    /// Lato ricevitore di un canale a cui arrivano esattamente `frames`
+    fn receiver_of(frames: &[Vec<u8>]) -> SecureChannel {
+        let (mut wire, server) = stream_pair();
+        for frame in frames {
+            wire.write_all(frame).unwrap();
+        }
+        channel(server, b"server->client", b"client->server")
+    }
+
+    #[test]
+    fn checked_path_accepts_relative_paths() {
+        for path in ["docs/report.pdf", ".group24/runs/1.json", "a..b/c", "file.txt"] {
+            assert_eq!(checked_path(path.to_string()).unwrap(), path);
+        }
+    }
+
+    #[test]
+    fn checked_path_rejects_paths_outside_the_destination() {
+        for path in [
+            "..",
+            "../etc/passwd",
+            "docs/../../secret",
+            "docs\\..\\..\\secret",
+            "/etc/passwd",
+            "\\\\server\\share\\file",
+            "C:\\Windows\\system.ini",
+            "docs/c:/file",
+        ] {
+            let error = checked_path(path.to_string()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::PermissionDenied, "{}", path);
+        }
+    }
+
+    #[test]
+    fn message_fields_round_trip() {
+        let mut message = Message::new(OP_RENAME).with_str("a.txt").with_u64(7).with_str("è.txt");
+        assert_eq!(message.take_str().unwrap(), "a.txt");
+        assert_eq!(message.take_u64().unwrap(), 7);
+        assert_eq!(message.take_str().unwrap(), "è.txt");
+        assert_eq!(message.take_u64().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let error = io::Error::new(io::ErrorKind::NotFound, "missing");
+        let error = Message::error(&error).expect(OP_OK).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(error.to_string(), "missing");
+    }
+
+    #[test]
+    fn handshake_and_messages_round_trip() {
+        let (port, receiver) = spawn_receiver([7; 32], |channel| {
+            let mut channel = channel.unwrap();
+            let mut request = channel.receive().unwrap().expect(OP_STAT).unwrap();
+            let path = request.take_str().unwrap();
+            let size = request.take_u64().unwrap();
+            channel.send(&Message::new(OP_ITEM).with_str(&path).with_u64(size * 2)).unwrap();
+            channel.send(&Message::data(&[1, 2, 3])).unwrap();
+        });
+
+        let mut channel = SecureChannel::connect("127.0.0.1", port, &test_key([7; 32])).unwrap();
+        channel.send(&Message::new(OP_STAT).with_str("docs/a.txt").with_u64(21)).unwrap();
+        let mut reply = channel.receive().unwrap().expect(OP_ITEM).unwrap();
+        assert_eq!(reply.take_str().unwrap(), "docs/a.txt");
+        assert_eq!(reply.take_u64().unwrap(), 42);
+        let data = channel.receive().unwrap().expect(OP_DATA).unwrap();
+        assert_eq!(data.into_data(), vec![1, 2, 3]);
+        receiver.join().unwrap();
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let (port, receiver) = spawn_receiver([7; 32], |channel| channel.is_err());
+        let error = SecureChannel::connect("127.0.0.1", port, &test_key([8; 32])).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(receiver.join().unwrap());
+    }
+
+    #[test]
+    fn frames_are_delivered_in_order() {
+        let frames = sealed_frames(&[Message::new(OP_OK), Message::data(b"chunk")]);
+        let mut receiver = receiver_of(&frames);
+        assert_eq!(receiver.receive().unwrap().kind, OP_OK);
+        assert_eq!(receiver.receive().unwrap().into_data(), b"chunk".to_vec());
+    }
+
+    #[test]
+    fn altered_frame_is_rejected() {
+        let mut frames = sealed_frames(&[Message::data(b"chunk")]);
+        let last = frames[0].len() - 1;
+        frames[0][last] ^= 0x01;
+        let error = receiver_of(&frames).receive().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reordered_or_replayed_frames_are_rejected() {
+        let frames = sealed_frames(&[Message::new(OP_OK), Message::new(OP_END)]);
+        let swapped = [frames[1].clone(), frames[0].clone()];
+        assert!(receiver_of(&swapped).receive().is_err());
+
+        let replayed = [frames[0].clone(), frames[0].clone()];
+        let mut receiver = receiver_of(&replayed);
+        assert_eq!(receiver.receive().unwrap().kind, OP_OK);
+        assert!(receiver.receive().is_err());
+    }
+
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let length = ((MAX_FRAME + 1) as u32).to_be_bytes().to_vec();
+        let error = receiver_of(&[length]).receive().err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
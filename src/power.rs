@@ -0,0 +1,86 @@
+use crate::utils::load_config;
+use systemstat::{Platform, System};
+
+/// Alimentazione attuale del computer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PowerState {
+    pub on_battery: bool,    // la batteria si sta scaricando (alimentatore scollegato)
+    pub charge: Option<f32>, // carica residua in percentuale (None senza batteria)
+}
+
+/// Legge lo stato da /sys/class/power_supply (alimentatori "Mains"/"USB" e batterie "Battery")
+#[cfg(target_os = "linux")]
+fn read_sysfs() -> Option<PowerState> {
+    use std::fs;
+    use std::path::Path;
+    fn read(path: &Path, name: &str) -> String {
+        fs::read_to_string(path.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    }
+
+    let mut ac_online = None;
+    let mut discharging = false;
+    let mut capacities = vec![];
+    for entry in fs::read_dir("/sys/class/power_supply").ok()?.flatten() {
+        let path = entry.path();
+        match read(&path, "type").as_str() {
+            "Mains" | "USB" => {
+                let online = read(&path, "online") == "1";
+                ac_online = Some(ac_online.unwrap_or(false) || online);
+            }
+            "Battery" => {
+                if let Ok(capacity) = read(&path, "capacity").parse::<f32>() {
+                    capacities.push(capacity);
+                }
+                discharging |= read(&path, "status") == "Discharging";
+            }
+            _ => {}
+        }
+    }
+    if capacities.is_empty() {
+        // Computer fisso (o nessuna informazione): si considera sempre alimentato
+        return None;
+    }
+    Some(PowerState {
+        on_battery: discharging || ac_online == Some(false),
+        charge: Some(capacities.iter().sum::<f32>() / capacities.len() as f32),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_sysfs() -> Option<PowerState> {
+    None
+}
+
+/// Stato dell'alimentazione; sugli altri sistemi usa le API di `systemstat`
+pub fn power_state() -> PowerState {
+    if let Some(state) = read_sysfs() {
+        return state;
+    }
+    let sys = System::new();
+    let charge = sys
+        .battery_life()
+        .ok()
+        .map(|battery| battery.remaining_capacity * 100.0);
+    PowerState {
+        on_battery: charge.is_some() && !sys.on_ac_power().unwrap_or(true),
+        charge,
+    }
+}
+
+/// Motivo per cui un job non urgente (programmato, per inattività, in coda) va rimandato,
+/// oppure None se può partire. Il backup avviato con il gesto non passa da qui.
+pub fn defer_reason() -> Option<String> {
+    let settings = load_config()?.power;
+    if !settings.defer_on_battery {
+        return None;
+    }
+    let state = power_state();
+    match state.charge {
+        Some(charge) if state.on_battery && charge < settings.min_battery_percent => {
+            Some(format!("on battery at {:.0}%", charge))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,128 @@
+use crate::detector::BackupLauncher;
+use crate::power;
+use crate::storage;
+use crate::utils::load_config;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+static QUEUE_PATH: &str = "backup_queue.toml"; // backup rimandati perché la destinazione non era disponibile
+static QUEUE_LOCK: Mutex<()> = Mutex::new(()); // il file viene modificato da più thread
+
+/// Backup automatico rimandato in attesa della destinazione
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QueuedJob {
+    pub trigger: String,   // cosa ha richiesto il backup (es. "schedule daily at 02:00")
+    pub queued_at: String, // data e ora della richiesta
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JobQueue {
+    #[serde(default)]
+    jobs: Vec<QueuedJob>,
+}
+
+fn load_queue() -> JobQueue {
+    fs::read_to_string(QUEUE_PATH)
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn store_queue(queue: &JobQueue) -> io::Result<()> {
+    let content = toml::to_string(queue)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+    fs::write(QUEUE_PATH, content)
+}
+
+/// Backup in attesa, dal più vecchio
+pub fn pending_jobs() -> Vec<QueuedJob> {
+    let _guard = QUEUE_LOCK.lock().unwrap();
+    load_queue().jobs
+}
+
+/// Aggiunge un backup alla coda; una richiesta dello stesso tipo già in attesa basta a recuperarlo
+pub fn enqueue(trigger: &str) {
+    let _guard = QUEUE_LOCK.lock().unwrap();
+    let mut queue = load_queue();
+    if queue.jobs.iter().any(|job| job.trigger == trigger) {
+        return;
+    }
+    queue.jobs.push(QueuedJob {
+        trigger: trigger.to_string(),
+        queued_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    });
+    if let Err(e) = store_queue(&queue) {
+        eprintln!("Impossibile salvare la coda dei backup: {}", e);
+    }
+}
+
+/// Rimuove un backup dalla coda (dalla GUI)
+pub fn remove(job: &QueuedJob) {
+    let _guard = QUEUE_LOCK.lock().unwrap();
+    let mut queue = load_queue();
+    queue.jobs.retain(|queued| queued != job);
+    if let Err(e) = store_queue(&queue) {
+        eprintln!("Impossibile salvare la coda dei backup: {}", e);
+    }
+}
+
+/// Svuota la coda: un backup appena avviato recupera tutti quelli in attesa
+pub fn clear() {
+    let _guard = QUEUE_LOCK.lock().unwrap();
+    if load_queue().jobs.is_empty() {
+        return;
+    }
+    if let Err(e) = store_queue(&JobQueue::default()) {
+        eprintln!("Impossibile salvare la coda dei backup: {}", e);
+    }
+}
+
+/// Vero se la destinazione principale è raggiungibile: i backup non urgenti non usano quelle di riserva
+pub fn destination_available() -> bool {
+    load_config()
+        .map(|config| storage::open_destination(&config).is_ok())
+        .unwrap_or(false)
+}
+
+/// Avvia subito un backup non urgente o, se la destinazione non è disponibile, il portatile
+/// sta andando a batteria o un altro job è in corso, lo mette in coda.
+/// Restituisce true solo se il backup è stato avviato.
+pub fn start_or_defer(launcher: &BackupLauncher, trigger: &str) -> bool {
+    if let Some(reason) = power::defer_reason() {
+        println!("Alimentazione insufficiente ({}): backup ({}) messo in coda.", reason, trigger);
+    } else if !destination_available() {
+        println!("Destinazione non disponibile: backup ({}) messo in coda.", trigger);
+    } else if launcher.start(trigger) {
+        return true;
+    } else {
+        println!("Un altro job è in corso: backup ({}) messo in coda.", trigger);
+    }
+    enqueue(trigger);
+    false
+}
+
+/// Controlla periodicamente la coda e avvia il backup appena la destinazione torna disponibile
+/// e l'alimentazione lo consente
+pub fn run_queue_worker(launcher: BackupLauncher) {
+    loop {
+        let jobs = pending_jobs();
+        launcher.state.lock().unwrap().pending_jobs = jobs.clone();
+
+        // I backup in coda ripartono quando torna la corrente (o la carica è sufficiente)
+        if !jobs.is_empty()
+            && launcher.is_idle()
+            && power::defer_reason().is_none()
+            && destination_available()
+            && launcher.start(&format!("{} queued backup(s) recovered", jobs.len()))
+        {
+            launcher.state.lock().unwrap().pending_jobs = vec![];
+        }
+
+        thread::sleep(Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::catalog;
+use crate::manifest::{self, ManifestEntry};
+use crate::parity;
+use crate::storage::{self, FileMeta, StorageBackend};
+use crate::transfer::{put_keeping_previous, unchanged_files};
+use crate::ui::MyApp;
+use crate::utils::{load_config, play_sound};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
+
+/// Riepilogo di una replica completata, mostrato nella finestra di fine operazione
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ReplicationReport {
+    pub source: String,
+    pub target: String,
+    pub runs_copied: Vec<String>,
+    pub files_copied: u64,
+    pub files_present: u64, // già presenti con lo stesso hash nella destinazione
+    pub files_missing: u64, // versioni non più presenti nella sorgente: escluse dai backup replicati
+    pub bytes_copied: u64,
+    pub duration_secs: u64,
+}
+
+impl ReplicationReport {
+    /// Righe di testo da mostrare all'utente
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("From: {}", self.source),
+            format!("To: {}", self.target),
+        ];
+        if self.runs_copied.is_empty() {
+            lines.push("The target already has every backup.".to_string());
+            return lines;
+        }
+        lines.push(format!("Backups replicated: {}", self.runs_copied.join(", ")));
+        lines.push(format!("Files copied: {}", self.files_copied));
+        lines.push(format!("Data copied: {} bytes", self.bytes_copied));
+        lines.push(format!("Files already present: {}", self.files_present));
+        if self.files_missing > 0 {
+            lines.push(format!(
+                "Older versions no longer in the source (left out of the replica): {}",
+                self.files_missing
+            ));
+        }
+        lines.push(format!("Duration: {}s", self.duration_secs));
+        lines
+    }
+}
+
+/// Backup presenti nella sorgente ma non nella destinazione, dal più vecchio
+pub fn missing_runs(source: &dyn StorageBackend, target: &dyn StorageBackend) -> Vec<String> {
+    let present: HashSet<String> = manifest::list_runs(target).into_iter().collect();
+    manifest::list_runs(source)
+        .into_iter()
+        .filter(|run_id| !present.contains(run_id))
+        .collect()
+}
+
+/// Copia un file e ne ricalcola l'hash all'arrivo; restituisce i byte copiati.
+/// La versione che il file sostituisce nella destinazione (`previous`) viene conservata.
+fn copy_verified(
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    from: &str,
+    to: &str,
+    meta: &FileMeta,
+    previous: Option<&str>,
+) -> io::Result<u64> {
+    let written = put_keeping_previous(target, to, &mut source.get(from)?, meta, previous)?;
+    if let Some(expected) = &meta.hash {
+        let arrived = storage::hash_stored(target, to)?;
+        if arrived != *expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Hash mismatch after copying `{}`.", from),
+            ));
+        }
+    }
+    Ok(written)
+}
+
+/// Contenuto delle due destinazioni, aggiornato man mano che i file vengono copiati
+struct Inventory {
+    source_files: HashSet<String>,
+    target_files: HashSet<String>,
+    source_hashes: HashMap<String, String>, // hash attuale dei file della sorgente, calcolato una sola volta
+    target_versions: HashMap<String, String>, // percorso -> hash della versione nella destinazione
+    present: HashMap<String, String>, // versioni già salvate nella destinazione con la dimensione registrata
+}
+
+/// Copia nella destinazione i backup che le mancano, un backup alla volta (manifest per ultimo,
+/// così un backup compare nella destinazione solo quando tutti i suoi file sono arrivati)
+pub fn replicate(
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    should_stop: &dyn Fn() -> bool,
+    progress: &mut dyn FnMut(f32, &str),
+) -> Result<ReplicationReport, String> {
+    let start_time = Instant::now();
+    let mut report = ReplicationReport {
+        source: source.describe(),
+        target: target.describe(),
+        ..Default::default()
+    };
+    let runs = missing_runs(source, target);
+    if runs.is_empty() {
+        return Ok(report);
+    }
+
+    let fail = |e: io::Error| format!("Replication failed: {}", e);
+    let manifests = runs
+        .iter()
+        .map(|run_id| manifest::read_manifest(source, run_id))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(fail)?;
+    let total: usize = manifests.iter().map(|run| run.files.len()).sum();
+
+    let paths = |backend: &dyn StorageBackend| -> io::Result<HashSet<String>> {
+        Ok(backend.list("")?.into_iter().map(|file| file.path).collect())
+    };
+    let target_latest = manifest::latest_entries(target).map_err(fail)?;
+    let mut inventory = Inventory {
+        source_files: paths(source).map_err(fail)?,
+        target_files: paths(target).map_err(fail)?,
+        source_hashes: HashMap::new(),
+        target_versions: target_latest
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.hash.clone()))
+            .collect(),
+        present: unchanged_files(target, &target_latest),
+    };
+
+    let mut done = 0;
+    for run in &manifests {
+        // Il manifest scritto nella destinazione elenca solo i file che la destinazione ha davvero
+        let mut replicated = run.clone();
+        replicated.files.clear();
+        for entry in &run.files {
+            if should_stop() {
+                return Err("stop".to_string());
+            }
+            progress(done as f32 / total as f32, &format!("{}: {}", run.run_id, entry.path));
+            done += 1;
+
+            let object = manifest::object_path(&entry.hash);
+            let kept = if inventory.present.get(&entry.path) == Some(&entry.hash)
+                || inventory.target_files.contains(&object)
+            {
+                report.files_present += 1;
+                true
+            } else {
+                copy_entry(source, target, entry, &mut inventory, &mut report).map_err(fail)?
+            };
+            if !kept {
+                continue;
+            }
+            replicated.files.push(entry.clone());
+
+            // La parità è indicizzata per hash: si copia se la sorgente ce l'ha e la destinazione no
+            let parity_file = parity::parity_path(&entry.hash);
+            if inventory.source_files.contains(&parity_file) && !inventory.target_files.contains(&parity_file) {
+                let meta = FileMeta {
+                    hash: Some(storage::hash_stored(source, &parity_file).map_err(fail)?),
+                    ..Default::default()
+                };
+                report.bytes_copied +=
+                    copy_verified(source, target, &parity_file, &parity_file, &meta, None).map_err(fail)?;
+                inventory.target_files.insert(parity_file);
+            }
+        }
+
+        manifest::write_manifest(target, &replicated).map_err(fail)?;
+        if let Err(e) = catalog::record_run(&report.target, &replicated) {
+            eprintln!("Impossibile aggiornare il catalogo dei backup: {}", e);
+        }
+        println!("Backup {} replicato su {}", run.run_id, report.target);
+        report.runs_copied.push(run.run_id.clone());
+    }
+    progress(1.0, "");
+    report.duration_secs = start_time.elapsed().as_secs();
+    Ok(report)
+}
+
+/// Copia un file di un backup se la sorgente ne conserva ancora la versione registrata, nel mirror
+/// o tra le versioni sostituite. Restituisce false se la versione non esiste più.
+fn copy_entry(
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    entry: &ManifestEntry,
+    inventory: &mut Inventory,
+    report: &mut ReplicationReport,
+) -> io::Result<bool> {
+    let source_hash = match inventory.source_hashes.get(&entry.path) {
+        Some(hash) => hash.clone(),
+        None => {
+            let hash = match storage::hash_stored(source, &entry.path) {
+                Ok(hash) => hash,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e),
+            };
+            inventory.source_hashes.insert(entry.path.clone(), hash.clone());
+            hash
+        }
+    };
+    let meta = FileMeta {
+        size: entry.size,
+        modified: entry.modified,
+        hash: Some(entry.hash.clone()),
+    };
+
+    if source_hash == entry.hash {
+        let previous = inventory.target_versions.get(&entry.path).cloned();
+        report.bytes_copied += copy_verified(source, target, &entry.path, &entry.path, &meta, previous.as_deref())?;
+        // La versione sostituita nella destinazione può essere stata spostata tra gli oggetti
+        if let Some(previous) = previous.filter(|previous| *previous != entry.hash) {
+            let object = manifest::object_path(&previous);
+            if target.stat(&object)?.is_some() {
+                inventory.target_files.insert(object);
+            }
+        }
+        inventory.target_versions.insert(entry.path.clone(), entry.hash.clone());
+        inventory.present.insert(entry.path.clone(), entry.hash.clone());
+    } else {
+        // La sorgente è un mirror: un backup successivo ha sostituito questa versione, che resta
+        // disponibile solo se è stata conservata tra le versioni sostituite
+        let object = manifest::object_path(&entry.hash);
+        if !inventory.source_files.contains(&object) {
+            report.files_missing += 1;
+            return Ok(false);
+        }
+        report.bytes_copied += copy_verified(source, target, &object, &object, &meta, None)?;
+        inventory.target_files.insert(object);
+        if let Err(e) = catalog::record_object(&report.target, &entry.hash) {
+            eprintln!("Impossibile aggiornare il catalogo dei backup: {}", e);
+        }
+    }
+    report.files_copied += 1;
+    Ok(true)
+}
+
+/// Replica configurata in [replication], eseguita come job nella finestra di avanzamento del backup
+pub fn perform_replication_with_stop(
+    stop_rx: &Receiver<String>,
+    state: &mut MyApp,
+) -> Result<ReplicationReport, String> {
+    let config = load_config().ok_or_else(|| {
+        "Configurazione non valida. Imposta una configurazione valida dal pannello di Backup!".to_string()
+    })?;
+    if config.replication.destination_type.is_empty() {
+        return Err("No replication target: add a [replication] section to config_build.toml.".to_string());
+    }
+    let source = storage::open_destination(&config)?;
+    let target = storage::open_extra(&config, &config.replication)?;
+
+    play_sound("Sounds/bubblepop-254773.mp3");
+    let should_stop = || matches!(stop_rx.try_recv().as_deref(), Ok("stop"));
+    let mut progress = |value: f32, current: &str| {
+        *state.progress.lock().unwrap() = value;
+        *state.current_file.lock().unwrap() = Some(current.to_string());
+    };
+    match replicate(source.as_ref(), target.as_ref(), &should_stop, &mut progress) {
+        Ok(report) => {
+            play_sound("Sounds/bellding-254774.mp3");
+            Ok(report)
+        }
+        Err(e) => {
+            play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use crate::cpu_gate::CpuGate;
+use crate::detector::BackupLauncher;
+use crate::power;
+use crate::queue;
+use crate::ui::JobKind;
+use crate::utils::{load_config, ScheduleConfig};
+use chrono::{Datelike, Duration, Local, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::Mutex;
+use std::thread;
+
+static STATE_PATH: &str = "schedule_state.toml"; // ultima esecuzione riuscita di ogni programmazione
+static STATE_LOCK: Mutex<()> = Mutex::new(());
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Serialize, Deserialize, Default)]
+struct ScheduleState {
+    #[serde(default)]
+    last_success: HashMap<String, String>, // chiave della programmazione -> data e ora
+}
+
+/// Quando eseguire un job: "every 4h", "daily at 02:00" o un'espressione cron a 5 campi
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    Every(Duration),
+    Daily(NaiveTime),
+    Cron(CronExpr),
+}
+
+/// Espressione cron: minuto ora giorno-del-mese mese giorno-della-settimana
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpr {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>, // 0 = domenica
+    any_day: bool,      // campo giorno del mese = "*"
+    any_weekday: bool,  // campo giorno della settimana = "*"
+}
+
+/// Valori ammessi da un campo cron (`*`, `5`, `1-5`, `*/15`, `1,15,30`)
+fn parse_cron_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = vec![];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step `{}`", step))?,
+            ),
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                start.parse().map_err(|_| format!("invalid value `{}`", start))?,
+                end.parse().map_err(|_| format!("invalid value `{}`", end))?,
+            )
+        } else {
+            let value = range.parse().map_err(|_| format!("invalid value `{}`", range))?;
+            // "5/10" significa da 5 fino al massimo, ogni 10
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("`{}` is out of range {}-{}", part, min, max));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+impl CronExpr {
+    fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err("a cron expression needs 5 fields".to_string());
+        }
+        // Per il giorno della settimana sia 0 sia 7 indicano la domenica
+        let mut weekdays: Vec<u32> = parse_cron_field(fields[4], 0, 7)?
+            .into_iter()
+            .map(|day| day % 7)
+            .collect();
+        weekdays.sort_unstable();
+        weekdays.dedup();
+        Ok(CronExpr {
+            minutes: parse_cron_field(fields[0], 0, 59)?,
+            hours: parse_cron_field(fields[1], 0, 23)?,
+            days: parse_cron_field(fields[2], 1, 31)?,
+            months: parse_cron_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    /// Come in cron, se sono indicati sia il giorno del mese sia quello della settimana basta uno dei due
+    fn matches_date(&self, date: chrono::NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+        let day = self.days.contains(&date.day());
+        let weekday = self.weekdays.contains(&date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        // Al massimo qualche anno in avanti (es. 29 febbraio)
+        for offset in 0..(366 * 5) {
+            let date = after.date() + Duration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+            for &hour in &self.hours {
+                for &minute in &self.minutes {
+                    let candidate = date.and_hms_opt(hour, minute, 0)?;
+                    if candidate > after {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Schedule {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim().to_lowercase();
+        if let Some(interval) = text.strip_prefix("every ") {
+            let interval = interval.replace(' ', "");
+            let split = interval
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(|| format!("missing unit in `{}`", text))?;
+            let (amount, unit) = interval.split_at(split);
+            let amount: i64 = amount.parse().map_err(|_| format!("invalid interval in `{}`", text))?;
+            let duration = match unit {
+                "m" | "min" | "mins" | "minute" | "minutes" => Duration::minutes(amount),
+                "h" | "hour" | "hours" => Duration::hours(amount),
+                "d" | "day" | "days" => Duration::days(amount),
+                _ => return Err(format!("unknown unit `{}` (use m, h or d)", unit)),
+            };
+            if duration < Duration::minutes(1) {
+                return Err("the interval must be at least one minute".to_string());
+            }
+            return Ok(Schedule::Every(duration));
+        }
+        if let Some(time) = text.strip_prefix("daily at ") {
+            let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| format!("invalid time in `{}` (use HH:MM)", text))?;
+            return Ok(Schedule::Daily(time));
+        }
+        CronExpr::parse(&text).map(Schedule::Cron)
+    }
+
+    /// Prima esecuzione successiva a `last` (l'ultima esecuzione, o l'avvio del programma)
+    pub fn next_after(&self, last: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Schedule::Every(interval) => Some(last + *interval),
+            Schedule::Daily(time) => {
+                let today = last.date().and_time(*time);
+                Some(if today > last {
+                    today
+                } else {
+                    today + Duration::days(1)
+                })
+            }
+            Schedule::Cron(cron) => cron.next_after(last.with_second(0)?.with_nanosecond(0)?),
+        }
+    }
+}
+
+/// Tipo di job indicato nella configurazione ("backup" se vuoto)
+fn job_kind(config: &ScheduleConfig) -> Result<JobKind, String> {
+    match config.job.as_str() {
+        "" | "backup" => Ok(JobKind::Backup),
+        "replication" => Ok(JobKind::Replication),
+        other => Err(format!("unknown job `{}`", other)),
+    }
+}
+
+fn job_name(job: &JobKind) -> &'static str {
+    match job {
+        JobKind::Backup => "backup",
+        JobKind::Replication => "replication",
+    }
+}
+
+/// Identifica una programmazione tra una lettura della configurazione e l'altra (e tra un avvio e l'altro)
+fn schedule_key(job: &JobKind, when: &str) -> String {
+    format!("{}|{}", job_name(job), when.trim())
+}
+
+fn load_state() -> ScheduleState {
+    fs::read_to_string(STATE_PATH)
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Ultima esecuzione riuscita salvata per ogni programmazione
+fn last_successes() -> HashMap<String, NaiveDateTime> {
+    load_state()
+        .last_success
+        .into_iter()
+        .filter_map(|(key, time)| {
+            NaiveDateTime::parse_from_str(&time, TIME_FORMAT)
+                .ok()
+                .map(|time| (key, time))
+        })
+        .collect()
+}
+
+/// Registra il successo di un job: vale per tutte le programmazioni dello stesso tipo,
+/// anche se il job è stato avviato dal gesto o da un'altra causa
+pub fn record_success(job: &JobKind) {
+    let _guard = STATE_LOCK.lock().unwrap();
+    let schedules = load_config().map(|config| config.schedule).unwrap_or_default();
+    let now = Local::now().format(TIME_FORMAT).to_string();
+    let mut state = load_state();
+    for config in &schedules {
+        if matches!(job_kind(config), Ok(kind) if kind == *job) {
+            state.last_success.insert(schedule_key(job, &config.when), now.clone());
+        }
+    }
+    let result = toml::to_string(&state)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+        .and_then(|content| fs::write(STATE_PATH, content));
+    if let Err(e) = result {
+        eprintln!("Impossibile salvare lo stato delle programmazioni: {}", e);
+    }
+}
+
+/// Esegue i job programmati in [[schedule]] con lo stesso motore e la stessa finestra del gesto.
+/// Il thread parte una sola volta per avvio del sistema (file di lock), quindi all'inizio
+/// recupera come anacron i job scaduti mentre il PC era spento.
+pub fn run_scheduler(launcher: BackupLauncher) {
+    let started = Local::now().naive_local();
+    // Esecuzioni avviate in questa sessione, anche se non ancora (o non) riuscite.
+    // Un job che non è partito resta scaduto e viene riprovato al controllo successivo.
+    let mut last_runs: HashMap<String, NaiveDateTime> = HashMap::new();
+    let mut cpu_gate = CpuGate::default();
+
+    loop {
+        let now = Local::now().naive_local();
+        let Some(settings) = load_config() else {
+            thread::sleep(std::time::Duration::from_secs(20));
+            continue;
+        };
+        let successes = last_successes();
+        let catch_up_at = started + Duration::minutes(settings.catch_up.delay_minutes.max(0));
+        let mut next_runs = vec![];
+
+        for config in &settings.schedule {
+            let (schedule, job) = match (Schedule::parse(&config.when), job_kind(config)) {
+                (Ok(schedule), Ok(job)) => (schedule, job),
+                (Err(e), _) | (_, Err(e)) => {
+                    next_runs.push(format!("Invalid schedule `{}`: {}", config.when, e));
+                    continue;
+                }
+            };
+            let key = schedule_key(&job, &config.when);
+            // Senza storico si conta dall'avvio del programma
+            let last = [last_runs.get(&key), successes.get(&key)]
+                .into_iter()
+                .flatten()
+                .max()
+                .copied()
+                .unwrap_or(started);
+            let Some(next) = schedule.next_after(last) else {
+                continue;
+            };
+
+            // Esecuzione scaduta prima dell'avvio: si recupera (dopo l'eventuale attesa) o si salta
+            let missed = next < started;
+            if missed && !settings.catch_up.enabled {
+                last_runs.insert(key, started);
+                continue;
+            }
+            let due = if missed { next.max(catch_up_at) } else { next };
+
+            if due <= now {
+                // Il job aspetta che il carico della CPU scenda (al massimo per il tempo configurato)
+                if !cpu_gate.ready(&key) {
+                    next_runs.push(format!(
+                        "{} - {} ({}, waiting for a lower CPU load)",
+                        due.format("%Y-%m-%d %H:%M"),
+                        job_name(&job),
+                        config.when
+                    ));
+                    continue;
+                }
+                let reason = if missed {
+                    println!("Recupero del job programmato `{}` saltato il {}.", config.when, next);
+                    format!("missed schedule {}", config.when)
+                } else {
+                    format!("schedule {}", config.when)
+                };
+                let waiting = match job {
+                    // I backup programmati non sono urgenti: se la destinazione manca, si è a batteria
+                    // o un altro job è in corso vanno in coda (e vengono riprovati finché non partono)
+                    JobKind::Backup => {
+                        if queue::start_or_defer(&launcher, &reason) {
+                            None
+                        } else {
+                            Some("queued".to_string())
+                        }
+                    }
+                    // La replica non ha una coda: si riprova al prossimo controllo
+                    JobKind::Replication => match power::defer_reason() {
+                        Some(power) => Some(power),
+                        None if launcher.start_job(JobKind::Replication, &reason) => None,
+                        None => Some("another job is running".to_string()),
+                    },
+                };
+                match waiting {
+                    None => {
+                        last_runs.insert(key, now);
+                    }
+                    Some(waiting) => next_runs.push(format!(
+                        "{} - {} ({}, waiting: {})",
+                        due.format("%Y-%m-%d %H:%M"),
+                        job_name(&job),
+                        config.when,
+                        waiting
+                    )),
+                }
+                continue;
+            }
+            next_runs.push(format!(
+                "{} - {} ({}{})",
+                due.format("%Y-%m-%d %H:%M"),
+                job_name(&job),
+                config.when,
+                if missed { ", missed" } else { "" }
+            ));
+        }
+
+        next_runs.sort();
+        launcher.state.lock().unwrap().next_scheduled = next_runs;
+        thread::sleep(std::time::Duration::from_secs(20));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn cron(expression: &str) -> CronExpr {
+        match Schedule::parse(expression).unwrap() {
+            Schedule::Cron(cron) => cron,
+            other => panic!("`{}` parsed as {:?}", expression, other),
+        }
+    }
+
+    #[test]
+    fn parses_cron_fields() {
+        assert_eq!(parse_cron_field("*/15", 0, 59).unwrap(), vec![0, 15, 30, 45]);
+        assert_eq!(parse_cron_field("50/5", 0, 59).unwrap(), vec![50, 55]);
+        assert_eq!(parse_cron_field("1-3,2,10-20/5", 0, 59).unwrap(), vec![1, 2, 3, 10, 15, 20]);
+        assert_eq!(parse_cron_field("7", 1, 12).unwrap(), vec![7]);
+    }
+
+    #[test]
+    fn rejects_invalid_cron_fields() {
+        assert!(parse_cron_field("60", 0, 59).is_err());
+        assert!(parse_cron_field("0", 1, 31).is_err());
+        assert!(parse_cron_field("5-1", 0, 59).is_err());
+        assert!(parse_cron_field("*/0", 0, 59).is_err());
+        assert!(parse_cron_field("a", 0, 59).is_err());
+        assert!(parse_cron_field("", 0, 59).is_err());
+        assert!(Schedule::parse("0 2 * *").is_err());
+        assert!(Schedule::parse("0 2 * * * *").is_err());
+    }
+
+    #[test]
+    fn sunday_is_both_zero_and_seven() {
+        assert_eq!(cron("0 0 * * 0").weekdays, vec![0]);
+        assert_eq!(cron("0 0 * * 7").weekdays, vec![0]);
+        assert_eq!(cron("0 0 * * 5-7").weekdays, vec![0, 5, 6]);
+    }
+
+    #[test]
+    fn parses_intervals_and_daily_times() {
+        assert_eq!(Schedule::parse("every 4h").unwrap(), Schedule::Every(Duration::hours(4)));
+        assert_eq!(Schedule::parse("Every 90 minutes").unwrap(), Schedule::Every(Duration::minutes(90)));
+        assert_eq!(Schedule::parse("every 2 days").unwrap(), Schedule::Every(Duration::days(2)));
+        assert!(Schedule::parse("every 0m").is_err());
+        assert!(Schedule::parse("every 30s").is_err());
+        assert!(Schedule::parse("every h").is_err());
+        assert_eq!(
+            Schedule::parse("daily at 02:00").unwrap(),
+            Schedule::Daily(NaiveTime::from_hms_opt(2, 0, 0).unwrap())
+        );
+        assert!(Schedule::parse("daily at 25:00").is_err());
+    }
+
+    #[test]
+    fn daily_runs_today_or_tomorrow() {
+        let schedule = Schedule::parse("daily at 02:00").unwrap();
+        assert_eq!(schedule.next_after(at(2024, 3, 10, 1, 0)), Some(at(2024, 3, 10, 2, 0)));
+        assert_eq!(schedule.next_after(at(2024, 3, 10, 2, 0)), Some(at(2024, 3, 11, 2, 0)));
+        assert_eq!(schedule.next_after(at(2024, 12, 31, 23, 0)), Some(at(2025, 1, 1, 2, 0)));
+    }
+
+    #[test]
+    fn cron_next_run_skips_the_current_minute() {
+        let schedule = Schedule::parse("*/15 * * * *").unwrap();
+        let last = at(2024, 3, 10, 10, 15).with_second(42).unwrap();
+        assert_eq!(schedule.next_after(last), Some(at(2024, 3, 10, 10, 30)));
+        assert_eq!(schedule.next_after(at(2024, 3, 10, 23, 45)), Some(at(2024, 3, 11, 0, 0)));
+    }
+
+    #[test]
+    fn cron_finds_the_next_leap_day() {
+        let schedule = Schedule::parse("0 3 29 2 *").unwrap();
+        assert_eq!(schedule.next_after(at(2023, 3, 1, 0, 0)), Some(at(2024, 2, 29, 3, 0)));
+    }
+
+    #[test]
+    fn cron_day_and_weekday_match_either() {
+        // Il 1° del mese oppure di lunedì; il 2024-03-02 è un sabato, il 2024-03-04 un lunedì
+        let either = cron("0 12 1 * 1");
+        assert!(either.matches_date(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap()));
+        assert!(either.matches_date(NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()));
+        assert!(!either.matches_date(NaiveDate::from_ymd_opt(2024, 3, 2).unwrap()));
+
+        // Con uno dei due campi a "*" conta solo l'altro
+        let weekdays = cron("0 12 * * 1-5");
+        assert!(!weekdays.matches_date(NaiveDate::from_ymd_opt(2024, 3, 2).unwrap()));
+        assert!(weekdays.matches_date(NaiveDate::from_ymd_opt(2024, 3, 4).unwrap()));
+    }
+
+    #[test]
+    fn cron_that_never_matches_has_no_next_run() {
+        let schedule = Schedule::parse("0 0 31 2 *").unwrap();
+        assert_eq!(schedule.next_after(at(2024, 1, 1, 0, 0)), None);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::analytics::{last_scrub_timestamp, log_scrub_result_to_csv};
+use crate::manifest;
+use crate::storage::{self, StorageBackend};
+use crate::ui::AppState;
+use crate::utils::{load_config, play_sound};
+use chrono::Local;
+use std::io;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Esito della verifica di una destinazione
+#[derive(Debug, Default)]
+pub struct ScrubResult {
+    pub checked: usize,
+    pub corrupted: Vec<String>, // percorsi relativi dei file mancanti o con hash diverso
+}
+
+/// Ricalcola l'hash di tutti i file salvati e lo confronta con quello registrato nei manifest
+pub fn scrub_destination(backend: &dyn StorageBackend) -> io::Result<ScrubResult> {
+    let mut result = ScrubResult::default();
+    for (path, entry) in manifest::latest_entries(backend)? {
+        result.checked += 1;
+        match storage::hash_stored(backend, &path) {
+            Ok(hash) if hash == entry.hash => {}
+            Ok(hash) => {
+                eprintln!(
+                    "Scrub: hash non corrispondente per {} (atteso {}, trovato {})",
+                    path, entry.hash, hash
+                );
+                result.corrupted.push(path);
+            }
+            Err(e) => {
+                eprintln!("Scrub: impossibile leggere {}: {}", path, e);
+                result.corrupted.push(path);
+            }
+        }
+    }
+    result.corrupted.sort();
+    Ok(result)
+}
+
+/// Ciclo di verifica periodica, eseguito dal processo in background accanto al logging della CPU
+pub fn run_periodic_scrub(shared_state: Arc<Mutex<AppState>>, tx: Sender<String>) {
+    let mut last_scrub = last_scrub_timestamp();
+
+    loop {
+        // La configurazione viene riletta ogni volta per applicare le modifiche fatte dalla GUI
+        if let Some(config) = load_config() {
+            let interval = chrono::Duration::hours(config.scrub.interval_hours as i64);
+            let due = match last_scrub {
+                Some(last) => Local::now().naive_local() - last >= interval,
+                None => true,
+            };
+            // Se la destinazione non è raggiungibile (es. chiavetta scollegata) si riprova più tardi
+            let backend = if config.scrub.enabled && due {
+                storage::open_destination(&config).ok()
+            } else {
+                None
+            };
+
+            if let Some(backend) = backend {
+                println!("Scrub della destinazione {} in corso...", backend.describe());
+                match scrub_destination(backend.as_ref()) {
+                    Ok(result) => {
+                        log_scrub_result_to_csv(result.checked, &result.corrupted);
+                        last_scrub = Some(Local::now().naive_local());
+                        println!(
+                            "Scrub completato: {} file verificati, {} corrotti.",
+                            result.checked,
+                            result.corrupted.len()
+                        );
+                        if !result.corrupted.is_empty() {
+                            raise_alert(&shared_state, &tx, &result);
+                        }
+                    }
+                    Err(e) => eprintln!("Scrub fallito: {}", e),
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_secs(60)); // Controlla ogni minuto se è ora di una nuova verifica
+    }
+}
+
+/// Segnala la corruzione all'utente con un suono e mostrando la GUI
+fn raise_alert(shared_state: &Arc<Mutex<AppState>>, tx: &Sender<String>, result: &ScrubResult) {
+    play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+
+    let mut state = shared_state.lock().unwrap();
+    state.scrub_alert = Some(format!(
+        "The periodic check found {} damaged or missing file(s) out of {}:\n{}",
+        result.corrupted.len(),
+        result.checked,
+        result.corrupted.join("\n")
+    ));
+    if !state.display {
+        if let Err(err) = tx.send("showGUI".to_string()) {
+            eprintln!("Failed to send showGUI message: {}", err);
+            state.display = false;
+        } else {
+            println!("Message sent to show GUI.");
+        }
+    }
+}
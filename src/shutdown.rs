@@ -0,0 +1,256 @@
+use crate::analytics::{log_guard_event_to_csv, log_shutdown_backup_to_csv};
+use crate::catalog;
+use crate::guard;
+use crate::manifest::{self, ManifestEntry, RunManifest};
+use crate::storage;
+use crate::transfer::{copy_single_file, is_included};
+use crate::utils::load_config;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static FINAL_BACKUP_DONE: Mutex<bool> = Mutex::new(false); // il backup finale va fatto una sola volta
+
+/// Tutti i file sotto `dir`, esclusa la cartella interna dell'applicazione
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != ".group24" {
+                collect_files(&path, files);
+            }
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+/// Backup incrementale rapido: copia i file cambiati rispetto all'ultimo backup finché non scade
+/// il tempo a disposizione, prima quelli nelle cartelle prioritarie e poi dal più recente.
+/// Ogni passo viene passato a `log` (evento, dettaglio). Restituisce i file salvati.
+/// Con modifiche sospette nella sorgente non viene copiato nulla: non c'è nessuno a confermarle.
+pub fn quick_backup(
+    budget: Duration,
+    priority_folders: &[String],
+    log: &dyn Fn(&str, &str),
+) -> Result<Vec<ManifestEntry>, String> {
+    let deadline = Instant::now() + budget;
+    let config = load_config().ok_or_else(|| "invalid configuration".to_string())?;
+    let backend = storage::open_with_fallback(&config)?.backend;
+    let source = Path::new(&config.source_folder);
+    if config.guard.enabled {
+        let include = |path: &Path| is_included(&config, path);
+        match guard::check_mass_change(source, backend.as_ref(), &include, &config.guard) {
+            Ok(Some(warning)) => {
+                log("guard", &warning);
+                log_guard_event_to_csv("skipped", &format!("quick backup: {}", warning));
+                return Err(format!("Quick backup skipped. {}", warning));
+            }
+            Ok(None) => {}
+            Err(e) => log("guard", &format!("check failed: {}", e)),
+        }
+    }
+    let latest = manifest::latest_entries(backend.as_ref()).unwrap_or_default();
+    let priority: Vec<PathBuf> = priority_folders.iter().map(|folder| source.join(folder)).collect();
+
+    let mut files = vec![];
+    collect_files(source, &mut files);
+    // Non c'è tempo per ricalcolare gli hash: si confrontano dimensione e data di modifica
+    let mut changed: Vec<(bool, u64, PathBuf)> = files
+        .into_iter()
+        .filter(|path| is_included(&config, path))
+        .filter_map(|path| {
+            let metadata = path.metadata().ok()?;
+            let modified = manifest::modified_secs(&metadata);
+            match latest.get(&manifest::relative_path(source, &path)) {
+                Some(entry) if entry.size == metadata.len() && entry.modified == modified => None,
+                _ => Some((priority.iter().any(|root| path.starts_with(root)), modified, path)),
+            }
+        })
+        .collect();
+    changed.sort_by(|a, b| (b.0, b.1).cmp(&(a.0, a.1)));
+    log("changed", &format!("{} files to copy", changed.len()));
+
+    let mut run = RunManifest {
+        run_id: manifest::new_run_id(),
+        source_folder: config.source_folder.clone(),
+        files: vec![],
+        partial: true,
+    };
+    for (_, _, path) in &changed {
+        if Instant::now() >= deadline {
+            log("timeout", &format!("{} files not copied", changed.len() - run.files.len()));
+            break;
+        }
+        match copy_single_file(backend.as_ref(), &config, path, &latest) {
+            Ok(entry) => {
+                log("copied", &entry.path);
+                run.files.push(entry);
+            }
+            Err(e) => log("failed", &format!("{:?}: {}", path, e)),
+        }
+    }
+
+    if !run.files.is_empty() {
+        manifest::write_manifest(backend.as_ref(), &run).map_err(|e| e.to_string())?;
+        if let Err(e) = catalog::record_run(&backend.describe(), &run) {
+            eprintln!("Impossibile aggiornare il catalogo dei backup: {}", e);
+        }
+    }
+    Ok(run.files)
+}
+
+/// Esegue il backup finale entro `budget` (una sola volta, anche se arrivano sia la notifica
+/// di logind sia il SIGTERM di fine sessione)
+pub fn final_backup(budget: Duration) {
+    let mut done = FINAL_BACKUP_DONE.lock().unwrap();
+    if *done {
+        return;
+    }
+    *done = true;
+    println!("Backup finale prima dello spegnimento ({}s a disposizione)...", budget.as_secs());
+    log_shutdown_backup_to_csv("start", &format!("time budget {}s", budget.as_secs()));
+    match quick_backup(budget, &[], &log_shutdown_backup_to_csv) {
+        Ok(copied) => log_shutdown_backup_to_csv("end", &format!("{} files copied", copied.len())),
+        Err(e) => log_shutdown_backup_to_csv("error", &e),
+    }
+}
+
+/// Chiamata dal gestore di SIGTERM (es. fine della sessione) prima di uscire
+pub fn backup_before_exit() {
+    let Some(settings) = load_config().map(|config| config.shutdown_backup) else {
+        return;
+    };
+    if settings.enabled {
+        final_backup(Duration::from_secs(settings.time_budget_secs));
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod logind {
+    use super::final_backup;
+    use crate::analytics::log_shutdown_backup_to_csv;
+    use crate::utils::load_config;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::thread;
+    use std::time::Duration;
+
+    /// Inibitore "delay" di logind: lo spegnimento attende finché il processo figlio è vivo
+    /// (`cat` termina appena gli si chiude lo standard input)
+    fn take_inhibitor() -> std::io::Result<Child> {
+        Command::new("systemd-inhibit")
+            .args([
+                "--what=shutdown",
+                "--mode=delay",
+                "--who=Group24 backup",
+                "--why=Final backup before shutdown",
+                "cat",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+    }
+
+    fn release_inhibitor(inhibitor: &mut Option<Child>) {
+        if let Some(mut child) = inhibitor.take() {
+            drop(child.stdin.take());
+            let _ = child.wait();
+        }
+    }
+
+    /// Ritardo massimo concesso da logind (InhibitDelayMaxSec, 5 secondi se non modificato)
+    fn max_delay() -> Option<Duration> {
+        let output = Command::new("busctl")
+            .args([
+                "get-property",
+                "org.freedesktop.login1",
+                "/org/freedesktop/login1",
+                "org.freedesktop.login1.Manager",
+                "InhibitDelayMaxUSec",
+            ])
+            .output()
+            .ok()?;
+        // Risposta nella forma "t 5000000"
+        let text = String::from_utf8_lossy(&output.stdout);
+        let micros: u64 = text.split_whitespace().nth(1)?.parse().ok()?;
+        Some(Duration::from_micros(micros))
+    }
+
+    /// Tiene un inibitore di logind e, quando arriva PrepareForShutdown, esegue il backup finale
+    /// prima di rilasciarlo
+    pub fn run_shutdown_guard() {
+        // Si attende che la funzione venga attivata dalla configurazione
+        while !load_config().map(|config| config.shutdown_backup.enabled).unwrap_or(false) {
+            thread::sleep(Duration::from_secs(60));
+        }
+
+        let mut monitor = match Command::new("dbus-monitor")
+            .args([
+                "--system",
+                "type='signal',interface='org.freedesktop.login1.Manager',member='PrepareForShutdown'",
+            ])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            Ok(monitor) => monitor,
+            Err(e) => {
+                eprintln!("Impossibile ascoltare le notifiche di spegnimento: {}", e);
+                return;
+            }
+        };
+        let mut inhibitor = match take_inhibitor() {
+            Ok(child) => Some(child),
+            Err(e) => {
+                eprintln!("Impossibile ottenere l'inibitore di logind: {}", e);
+                None
+            }
+        };
+
+        let Some(stdout) = monitor.stdout.take() else {
+            return;
+        };
+        let mut signal_seen = false;
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if line.contains("member=PrepareForShutdown") {
+                signal_seen = true;
+                continue;
+            }
+            if !signal_seen {
+                continue;
+            }
+            signal_seen = false;
+            if line.trim() == "boolean true" {
+                let settings = load_config().map(|config| config.shutdown_backup).unwrap_or_default();
+                if settings.enabled {
+                    let mut budget = Duration::from_secs(settings.time_budget_secs);
+                    if let Some(max) = max_delay().filter(|max| *max < budget) {
+                        log_shutdown_backup_to_csv(
+                            "budget",
+                            &format!("limited to {}s by InhibitDelayMaxSec", max.as_secs()),
+                        );
+                        budget = max;
+                    }
+                    final_backup(budget);
+                }
+                release_inhibitor(&mut inhibitor);
+            } else if line.trim() == "boolean false" && inhibitor.is_none() {
+                // Spegnimento annullato: si torna a trattenerlo e il prossimo avrà il suo backup finale
+                *super::FINAL_BACKUP_DONE.lock().unwrap() = false;
+                inhibitor = take_inhibitor().ok();
+            }
+        }
+        release_inhibitor(&mut inhibitor);
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use logind::run_shutdown_guard;
+
+/// Su Windows e macOS il backup finale viene fatto solo alla ricezione di SIGTERM
+#[cfg(not(target_os = "linux"))]
+pub fn run_shutdown_guard() {}
@@ -0,0 +1,146 @@
+use super::{FileMeta, ObjectInfo, StorageBackend};
+use crate::manifest;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Destinazione su una cartella locale (disco interno, chiavetta USB, ...)
+pub struct LocalStorage {
+    root: PathBuf,
+    description: Option<String>, // descrizione stabile se la cartella può cambiare percorso (es. volume rimovibile)
+}
+
+impl LocalStorage {
+    /// Apre la cartella di destinazione, che deve già esistere
+    pub fn open(root: &Path) -> Result<Self, String> {
+        if !root.is_dir() {
+            return Err(format!(
+                "Destination folder: `{}` does not exist.",
+                root.display()
+            ));
+        }
+        Ok(LocalStorage {
+            root: root.to_path_buf(),
+            description: None,
+        })
+    }
+
+    /// Come `open`, ma la destinazione viene descritta (e catalogata) con `description` invece che con il percorso
+    pub fn open_described(root: &Path, description: String) -> Result<Self, String> {
+        let mut storage = Self::open(root)?;
+        storage.description = Some(description);
+        Ok(storage)
+    }
+
+    fn full_path(&self, path: &str) -> PathBuf {
+        let mut full = self.root.clone();
+        for component in path.split('/').filter(|c| !c.is_empty()) {
+            full.push(component);
+        }
+        full
+    }
+
+    fn list_folder(&self, folder: &Path, files: &mut Vec<ObjectInfo>) -> io::Result<()> {
+        for entry in fs::read_dir(folder)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.list_folder(&path, files)?;
+            } else if path.is_file() {
+                let metadata = path.metadata()?;
+                files.push(ObjectInfo {
+                    path: manifest::relative_path(&self.root, &path),
+                    size: metadata.len(),
+                    modified: manifest::modified_secs(&metadata),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn describe(&self) -> String {
+        match &self.description {
+            Some(description) => description.clone(),
+            None => self.root.to_string_lossy().to_string(),
+        }
+    }
+
+    fn put(&self, path: &str, data: &mut dyn Read, meta: &FileMeta) -> io::Result<u64> {
+        let target = self.full_path(path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Scrive su un file temporaneo e lo rinomina solo se la copia è integra
+        let mut temp = target.clone().into_os_string();
+        temp.push(".g24tmp");
+        let temp = PathBuf::from(temp);
+        let mut file = File::create(&temp)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut written = 0u64;
+        loop {
+            let read = data.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read])?;
+            hasher.update(&buffer[..read]);
+            written += read as u64;
+        }
+        file.sync_all()?;
+        drop(file);
+
+        if let Some(expected) = &meta.hash {
+            if format!("{:x}", hasher.finalize()) != *expected {
+                let _ = fs::remove_file(&temp);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Copy verification failed for `{}`.", path),
+                ));
+            }
+        }
+        fs::rename(&temp, &target)?;
+        Ok(written)
+    }
+
+    fn get(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(self.full_path(path))?))
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<ObjectInfo>> {
+        let folder = self.full_path(prefix);
+        let mut files = vec![];
+        if folder.is_dir() {
+            self.list_folder(&folder, &mut files)?;
+        }
+        Ok(files)
+    }
+
+    fn stat(&self, path: &str) -> io::Result<Option<ObjectInfo>> {
+        match fs::metadata(self.full_path(path)) {
+            Ok(metadata) if metadata.is_file() => Ok(Some(ObjectInfo {
+                path: path.to_string(),
+                size: metadata.len(),
+                modified: manifest::modified_secs(&metadata),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn delete(&self, path: &str) -> io::Result<()> {
+        fs::remove_file(self.full_path(path))
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let target = self.full_path(to);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(self.full_path(from), target)
+    }
+}
@@ -0,0 +1,166 @@
+pub mod local;
+pub mod peer;
+pub mod s3;
+pub mod sftp;
+pub mod webdav;
+mod xml;
+
+pub use local::LocalStorage;
+pub use peer::PeerStorage;
+pub use s3::S3Storage;
+pub use sftp::SftpStorage;
+pub use webdav::WebDavStorage;
+
+use crate::manifest;
+use crate::utils::{load_config, Config, ExtraDestination};
+use crate::volumes;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+/// Informazioni su un file salvato nella destinazione
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    pub path: String,  // percorso relativo alla radice della destinazione (separatore '/')
+    pub size: u64,
+    pub modified: u64, // secondi dall'epoch
+}
+
+/// Metadati del file sorgente che accompagnano il salvataggio
+#[derive(Debug, Clone, Default)]
+pub struct FileMeta {
+    pub size: u64,
+    pub modified: u64,
+    pub hash: Option<String>, // SHA-256 atteso: le destinazioni che possono verificarlo lo controllano
+}
+
+/// Operazioni che ogni tipo di destinazione deve offrire al motore di backup.
+/// Tutti i percorsi sono relativi alla radice della destinazione e usano '/' come separatore.
+pub trait StorageBackend: Send {
+    /// Descrizione leggibile della destinazione (percorso o URL)
+    fn describe(&self) -> String;
+    /// Salva un file, creando le cartelle intermedie se necessario; restituisce i byte scritti
+    fn put(&self, path: &str, data: &mut dyn Read, meta: &FileMeta) -> io::Result<u64>;
+    fn get(&self, path: &str) -> io::Result<Box<dyn Read + Send>>;
+    /// Elenca ricorsivamente i file sotto `prefix` (stringa vuota = tutta la destinazione)
+    fn list(&self, prefix: &str) -> io::Result<Vec<ObjectInfo>>;
+    /// Restituisce `None` se il file non esiste
+    fn stat(&self, path: &str) -> io::Result<Option<ObjectInfo>>;
+    fn delete(&self, path: &str) -> io::Result<()>;
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+}
+
+/// Descrizione della destinazione remota configurata, senza collegarsi
+/// (None per le destinazioni scelte dal Backup Panel: cartella locale o volume)
+pub fn remote_description(config: &Config) -> Option<String> {
+    match config.destination_type.as_str() {
+        "" | "local" | "volume" => None,
+        "sftp" => Some(format!(
+            "sftp://{}@{}:{}{}",
+            config.sftp.user, config.sftp.host, config.sftp.port, config.sftp.remote_path
+        )),
+        "s3" => Some(format!(
+            "s3://{}/{} ({})",
+            config.s3.bucket,
+            config.s3.prefix.trim_matches('/'),
+            config.s3.endpoint
+        )),
+        "webdav" => Some(config.webdav.url.clone()),
+        "peer" => Some(format!("peer://{}:{}", config.peer.host, config.peer.port)),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Crea la destinazione descritta dalla configurazione
+pub fn open_destination(config: &Config) -> Result<Box<dyn StorageBackend>, String> {
+    match config.destination_type.as_str() {
+        "" | "local" => Ok(Box::new(LocalStorage::open(Path::new(&config.destination_folder))?)),
+        "sftp" => Ok(Box::new(SftpStorage::connect(&config.sftp)?)),
+        "s3" => Ok(Box::new(S3Storage::connect(&config.s3)?)),
+        "webdav" => Ok(Box::new(WebDavStorage::connect(&config.webdav)?)),
+        "peer" => Ok(Box::new(PeerStorage::connect(&config.peer)?)),
+        "volume" => {
+            // Il punto di montaggio viene cercato ad ogni apertura: può cambiare da un collegamento all'altro
+            let folder = volumes::resolve(&config.volume)?;
+            Ok(Box::new(LocalStorage::open_described(&folder, volumes::describe(&config.volume))?))
+        }
+        other => Err(format!("Unknown destination type `{}`.", other)),
+    }
+}
+
+/// Crea una destinazione aggiuntiva; i tipi remoti usano le sezioni della configurazione principale
+pub fn open_extra(config: &Config, extra: &ExtraDestination) -> Result<Box<dyn StorageBackend>, String> {
+    let mut candidate = config.clone();
+    candidate.destination_type = extra.destination_type.clone();
+    candidate.destination_folder = extra.destination_folder.clone();
+    candidate.volume = extra.volume.clone();
+    open_destination(&candidate)
+}
+
+/// Destinazione effettivamente usata da un backup
+pub struct OpenedDestination {
+    pub backend: Box<dyn StorageBackend>,
+    pub tier: usize,  // 0 = destinazione principale, 1.. = destinazioni di riserva in ordine
+    pub name: String, // nome da annunciare all'utente
+}
+
+/// Prova la destinazione principale e poi quelle di riserva, nell'ordine della configurazione
+pub fn open_with_fallback(config: &Config) -> Result<OpenedDestination, String> {
+    let mut errors = vec![];
+    match open_destination(config) {
+        Ok(backend) => {
+            return Ok(OpenedDestination {
+                backend,
+                tier: 0,
+                name: "primary destination".to_string(),
+            })
+        }
+        Err(e) => errors.push(format!("Primary destination: {}", e)),
+    }
+
+    for (index, fallback) in config.fallback.iter().enumerate() {
+        let name = if fallback.name.is_empty() {
+            format!("fallback destination {}", index + 1)
+        } else {
+            fallback.name.clone()
+        };
+        match open_extra(config, fallback) {
+            Ok(backend) => {
+                println!("Destinazione principale non disponibile, uso {}", backend.describe());
+                return Ok(OpenedDestination {
+                    backend,
+                    tier: index + 1,
+                    name,
+                });
+            }
+            Err(e) => errors.push(format!("{}: {}", name, e)),
+        }
+    }
+    Err(format!("No destination is reachable.\n{}", errors.join("\n")))
+}
+
+/// Crea la destinazione salvata nel file di configurazione
+pub fn open_configured() -> Result<Box<dyn StorageBackend>, String> {
+    let config = load_config().ok_or_else(|| {
+        "Configurazione non valida. Imposta una configurazione valida dal pannello di Backup!".to_string()
+    })?;
+    open_destination(&config)
+}
+
+pub fn put_bytes(backend: &dyn StorageBackend, path: &str, bytes: &[u8]) -> io::Result<u64> {
+    let meta = FileMeta {
+        size: bytes.len() as u64,
+        ..Default::default()
+    };
+    backend.put(path, &mut Cursor::new(bytes), &meta)
+}
+
+pub fn read_to_string(backend: &dyn StorageBackend, path: &str) -> io::Result<String> {
+    let mut content = String::new();
+    backend.get(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// SHA-256 di un file salvato nella destinazione
+pub fn hash_stored(backend: &dyn StorageBackend, path: &str) -> io::Result<String> {
+    manifest::hash_reader(&mut backend.get(path)?)
+}
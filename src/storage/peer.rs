@@ -0,0 +1,301 @@
+use super::{FileMeta, ObjectInfo, StorageBackend};
+use crate::peer::{
+    Message, PeerKey, SecureChannel, CHUNK_SIZE, OP_ACK, OP_DATA, OP_DELETE, OP_END, OP_GET, OP_ITEM, OP_LIST,
+    OP_NONE, OP_OFFSET, OP_OK, OP_PUT, OP_RENAME, OP_STAT,
+};
+use crate::utils::PeerConfig;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+const MAX_ATTEMPTS: usize = 3; // tentativi per richiesta quando la connessione cade
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const RESEND_BLOCKS: usize = 64; // blocchi già inviati conservati per ritrasmetterli dopo una riconnessione (16 MiB)
+
+/// Destinazione su un altro PC avviato in modalità ricevitore (`backup_app_group24 receive`)
+pub struct PeerStorage {
+    host: String,
+    port: u16,
+    key: PeerKey,
+    channel: Mutex<Option<SecureChannel>>, // connessione per tutte le richieste tranne la lettura dei file (None se caduta)
+}
+
+/// Errori dopo i quali ha senso ricollegarsi e riprovare
+fn connection_lost(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::NotConnected
+    )
+}
+
+/// Dati letti dal file sorgente durante un invio. Il lettore non può tornare indietro: gli ultimi
+/// blocchi inviati vengono conservati per ritrasmettere quelli persi se la connessione cade.
+#[derive(Default)]
+struct SentData {
+    position: u64,             // byte letti dal file sorgente
+    recent: VecDeque<Vec<u8>>, // ultimi blocchi inviati, che terminano a `position`
+}
+
+impl SentData {
+    fn push(&mut self, block: Vec<u8>) {
+        self.position += block.len() as u64;
+        self.recent.push_back(block);
+        if self.recent.len() > RESEND_BLOCKS {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Dati da ritrasmettere a partire da `offset` (byte salvati dal ricevitore),
+    /// oppure None se sono più vecchi dei blocchi conservati
+    fn since(&self, offset: u64) -> Option<Vec<&[u8]>> {
+        let kept: u64 = self.recent.iter().map(|block| block.len() as u64).sum();
+        let mut start = self.position - kept;
+        if offset < start || offset > self.position {
+            return None;
+        }
+        let mut blocks = vec![];
+        for block in &self.recent {
+            let end = start + block.len() as u64;
+            if end > offset {
+                blocks.push(&block[offset.saturating_sub(start) as usize..]);
+            }
+            start = end;
+        }
+        Some(blocks)
+    }
+}
+
+/// Invia un file sulla connessione indicata riprendendo dai byte che il ricevitore ha già salvato,
+/// anche quelli di un tentativo precedente nella stessa esecuzione
+fn send_file(
+    channel: &mut SecureChannel,
+    path: &str,
+    data: &mut dyn Read,
+    meta: &FileMeta,
+    sent: &mut SentData,
+) -> io::Result<u64> {
+    // Gli errori di lettura del file locale non vanno confusi con una connessione caduta
+    let local = |e: io::Error| io::Error::new(io::ErrorKind::Other, format!("unable to read `{}`: {}", path, e));
+    channel.send(
+        &Message::new(OP_PUT)
+            .with_str(path)
+            .with_u64(meta.size)
+            .with_u64(meta.modified)
+            .with_str(meta.hash.as_deref().unwrap_or("")),
+    )?;
+
+    let offset = channel.receive()?.expect(OP_OFFSET)?.take_u64()?;
+    if offset >= sent.position {
+        // Salta la parte già ricevuta in un tentativo interrotto
+        let missing = offset - sent.position;
+        let skipped = io::copy(&mut (&mut *data).take(missing), &mut io::sink()).map_err(local)?;
+        if skipped != missing {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`{}` is shorter than the part already received", path),
+            ));
+        }
+        sent.position = offset;
+        sent.recent.clear();
+    } else {
+        // Il ricevitore ha perso gli ultimi blocchi inviati prima della disconnessione
+        let blocks = sent.since(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("unable to resume `{}`: too much data was lost with the connection", path),
+            )
+        })?;
+        for block in blocks {
+            channel.send(&Message::data(block))?;
+        }
+    }
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = data.read(&mut buffer).map_err(local)?;
+        if read == 0 {
+            break;
+        }
+        channel.send(&Message::data(&buffer[..read]))?;
+        sent.push(buffer[..read].to_vec());
+    }
+    channel.send(&Message::new(OP_END))?;
+
+    // Conferma per file: il ricevitore risponde solo dopo aver verificato e salvato la copia
+    channel.receive()?.expect(OP_ACK)?.take_u64()
+}
+
+impl PeerStorage {
+    /// Si collega al ricevitore; la connessione fallisce se una delle due parti non conosce la chiave
+    pub fn connect(config: &PeerConfig) -> Result<Self, String> {
+        if config.host.is_empty() || config.key.is_empty() {
+            return Err("Peer destination is incomplete: host and pre-shared key are required.".to_string());
+        }
+        let key = PeerKey::new(&config.key);
+        let channel = SecureChannel::connect(&config.host, config.port, &key).map_err(|e| {
+            format!(
+                "Unable to connect to receiver {}:{}: {}",
+                config.host, config.port, e
+            )
+        })?;
+        Ok(PeerStorage {
+            host: config.host.clone(),
+            port: config.port,
+            key,
+            channel: Mutex::new(Some(channel)),
+        })
+    }
+
+    /// Esegue `operation` sulla connessione principale, ricollegandosi se è caduta e riprovando
+    /// fino a `MAX_ATTEMPTS` volte. Dopo un errore la connessione viene chiusa: potrebbe essere
+    /// rimasta a metà di uno scambio di messaggi.
+    fn with_channel<T>(&self, mut operation: impl FnMut(&mut SecureChannel) -> io::Result<T>) -> io::Result<T> {
+        let mut attempt = 1;
+        loop {
+            let result = {
+                let mut channel = self.channel.lock().unwrap();
+                let connected = match channel.take() {
+                    Some(connected) => Ok(connected),
+                    None => SecureChannel::connect(&self.host, self.port, &self.key),
+                };
+                connected.and_then(|mut connected| {
+                    let result = operation(&mut connected);
+                    if result.is_ok() {
+                        *channel = Some(connected);
+                    }
+                    result
+                })
+            };
+            match result {
+                Err(e) if attempt < MAX_ATTEMPTS && connection_lost(&e) => {
+                    eprintln!("Connessione con {} interrotta ({}): nuovo tentativo...", self.describe(), e);
+                    thread::sleep(RETRY_DELAY);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Invia una richiesta e attende la risposta del tipo indicato
+    fn request(&self, message: &Message, reply: u8) -> io::Result<Message> {
+        self.with_channel(|channel| {
+            channel.send(message)?;
+            channel.receive()?.expect(reply)
+        })
+    }
+}
+
+fn object_info(mut item: Message) -> io::Result<ObjectInfo> {
+    Ok(ObjectInfo {
+        path: item.take_str()?,
+        size: item.take_u64()?,
+        modified: item.take_u64()?,
+    })
+}
+
+/// Contenuto di un file letto dal ricevitore su una connessione dedicata
+struct PeerReader {
+    channel: SecureChannel,
+    buffer: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl PeerReader {
+    /// Riceve il blocco successivo; restituisce false alla fine del file
+    fn fill(&mut self) -> io::Result<bool> {
+        let message = self.channel.receive()?;
+        if message.kind == OP_END {
+            self.done = true;
+            return Ok(false);
+        }
+        self.buffer = message.expect(OP_DATA)?.into_data();
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl Read for PeerReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.buffer.len() {
+            if self.done || !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let count = buffer.len().min(self.buffer.len() - self.pos);
+        buffer[..count].copy_from_slice(&self.buffer[self.pos..self.pos + count]);
+        self.pos += count;
+        Ok(count)
+    }
+}
+
+impl StorageBackend for PeerStorage {
+    fn describe(&self) -> String {
+        format!("peer://{}:{}", self.host, self.port)
+    }
+
+    fn put(&self, path: &str, data: &mut dyn Read, meta: &FileMeta) -> io::Result<u64> {
+        let mut sent = SentData::default();
+        self.with_channel(|channel| send_file(channel, path, &mut *data, meta, &mut sent))
+    }
+
+    fn get(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        // Connessione separata: il contenuto viene letto mentre l'altra resta libera per le richieste
+        let mut channel = SecureChannel::connect(&self.host, self.port, &self.key)?;
+        channel.send(&Message::new(OP_GET).with_str(path))?;
+        let mut reader = PeerReader {
+            channel,
+            buffer: vec![],
+            pos: 0,
+            done: false,
+        };
+        // Il primo messaggio indica già se il file esiste
+        reader.fill()?;
+        Ok(Box::new(reader))
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<ObjectInfo>> {
+        self.with_channel(|channel| {
+            channel.send(&Message::new(OP_LIST).with_str(prefix))?;
+            let mut files = vec![];
+            loop {
+                let message = channel.receive()?;
+                if message.kind == OP_END {
+                    return Ok(files);
+                }
+                files.push(object_info(message.expect(OP_ITEM)?)?);
+            }
+        })
+    }
+
+    fn stat(&self, path: &str) -> io::Result<Option<ObjectInfo>> {
+        self.with_channel(|channel| {
+            channel.send(&Message::new(OP_STAT).with_str(path))?;
+            let message = channel.receive()?;
+            if message.kind == OP_NONE {
+                return Ok(None);
+            }
+            object_info(message.expect(OP_ITEM)?).map(Some)
+        })
+    }
+
+    fn delete(&self, path: &str) -> io::Result<()> {
+        self.request(&Message::new(OP_DELETE).with_str(path), OP_OK)?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.request(&Message::new(OP_RENAME).with_str(from).with_str(to), OP_OK)?;
+        Ok(())
+    }
+}
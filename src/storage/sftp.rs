@@ -0,0 +1,241 @@
+use super::{FileMeta, ObjectInfo, StorageBackend};
+use crate::utils::SftpConfig;
+use sha2::{Digest, Sha256};
+use ssh2::{FileStat, Session, Sftp};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const SESSION_TIMEOUT_MS: u32 = 60_000;
+
+/// Destinazione su un server SSH (es. NAS domestico) raggiunto tramite SFTP
+pub struct SftpStorage {
+    _session: Session, // la sessione deve restare aperta finché si usa il canale SFTP
+    sftp: Sftp,
+    root: String, // cartella remota di destinazione, senza '/' finale
+    description: String,
+}
+
+impl SftpStorage {
+    /// Si collega al server con autenticazione a chiave e prepara la cartella remota
+    pub fn connect(config: &SftpConfig) -> Result<Self, String> {
+        let description = format!(
+            "sftp://{}@{}:{}{}",
+            config.user, config.host, config.port, config.remote_path
+        );
+        let fail = |e: &dyn std::fmt::Display| {
+            format!(
+                "Unable to connect to SFTP server {}:{}: {}",
+                config.host, config.port, e
+            )
+        };
+
+        if config.host.is_empty() || config.user.is_empty() || config.remote_path.is_empty() {
+            return Err("SFTP destination is incomplete: host, user and remote path are required.".to_string());
+        }
+
+        let address = (config.host.as_str(), config.port)
+            .to_socket_addrs()
+            .map_err(|e| fail(&e))?
+            .next()
+            .ok_or_else(|| fail(&"host not found"))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| fail(&e))?;
+
+        let mut session = Session::new().map_err(|e| fail(&e))?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(SESSION_TIMEOUT_MS);
+        session.handshake().map_err(|e| fail(&e))?;
+        let passphrase = if config.key_passphrase.is_empty() {
+            None
+        } else {
+            Some(config.key_passphrase.as_str())
+        };
+        session
+            .userauth_pubkey_file(&config.user, None, Path::new(&config.key_file), passphrase)
+            .map_err(|e| fail(&format!("authentication failed ({})", e)))?;
+        if !session.authenticated() {
+            return Err(fail(&"authentication failed"));
+        }
+        let sftp = session.sftp().map_err(|e| fail(&e))?;
+
+        let storage = SftpStorage {
+            _session: session,
+            sftp,
+            root: config.remote_path.trim_end_matches('/').to_string(),
+            description,
+        };
+        storage
+            .create_dirs(&storage.root)
+            .map_err(|e| format!("Unable to create remote folder `{}`: {}", config.remote_path, e))?;
+        Ok(storage)
+    }
+
+    fn remote(&self, path: &str) -> String {
+        format!("{}/{}", self.root, path.trim_start_matches('/'))
+    }
+
+    /// Crea tutte le cartelle mancanti del percorso remoto indicato
+    fn create_dirs(&self, folder: &str) -> io::Result<()> {
+        // Un percorso relativo viene risolto dal server a partire dalla home dell'utente
+        let mut current = if folder.starts_with('/') { "/".to_string() } else { String::new() };
+        for component in folder.split('/').filter(|c| !c.is_empty()) {
+            if !current.is_empty() && !current.ends_with('/') {
+                current.push('/');
+            }
+            current.push_str(component);
+            match self.sftp.stat(Path::new(&current)) {
+                Ok(stat) if stat.is_dir() => {}
+                Ok(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("`{}` is not a folder", current),
+                    ))
+                }
+                Err(_) => self.sftp.mkdir(Path::new(&current), 0o755)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn create_parent(&self, remote: &str) -> io::Result<()> {
+        match remote.rsplit_once('/') {
+            Some((parent, _)) if !parent.is_empty() => self.create_dirs(parent),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sostituisce `to` con `from`: OpenSSH non sovrascrive con il rename, quindi il file esistente va rimosso prima
+    fn replace(&self, from: &str, to: &str) -> io::Result<()> {
+        match self.sftp.unlink(Path::new(to)) {
+            Ok(()) => {}
+            Err(e) => {
+                let e = io::Error::from(e);
+                if e.kind() != io::ErrorKind::NotFound {
+                    return Err(e);
+                }
+            }
+        }
+        self.sftp.rename(Path::new(from), Path::new(to), None)?;
+        Ok(())
+    }
+
+    fn list_folder(&self, folder: &str, files: &mut Vec<ObjectInfo>) -> io::Result<()> {
+        for (path, stat) in self.sftp.readdir(Path::new(folder))? {
+            let path = path.to_string_lossy().replace('\\', "/");
+            if stat.is_dir() {
+                self.list_folder(&path, files)?;
+            } else if stat.is_file() {
+                files.push(ObjectInfo {
+                    path: path
+                        .strip_prefix(&format!("{}/", self.root))
+                        .unwrap_or(&path)
+                        .to_string(),
+                    size: stat.size.unwrap_or(0),
+                    modified: stat.mtime.unwrap_or(0),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StorageBackend for SftpStorage {
+    fn describe(&self) -> String {
+        self.description.clone()
+    }
+
+    fn put(&self, path: &str, data: &mut dyn Read, meta: &FileMeta) -> io::Result<u64> {
+        let target = self.remote(path);
+        self.create_parent(&target)?;
+
+        // Come per le cartelle locali: si scrive su un file temporaneo e lo si rinomina solo se la copia è integra
+        let temp = format!("{}.g24tmp", target);
+        let mut file = self.sftp.create(Path::new(&temp))?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut written = 0u64;
+        loop {
+            let read = data.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read])?;
+            hasher.update(&buffer[..read]);
+            written += read as u64;
+        }
+        drop(file);
+
+        if let Some(expected) = &meta.hash {
+            if format!("{:x}", hasher.finalize()) != *expected {
+                let _ = self.sftp.unlink(Path::new(&temp));
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Copy verification failed for `{}`.", path),
+                ));
+            }
+        }
+        // Conserva la data di modifica originale, usata per i confronti incrementali
+        if meta.modified > 0 {
+            let stat = FileStat {
+                size: None,
+                uid: None,
+                gid: None,
+                perm: None,
+                atime: Some(meta.modified),
+                mtime: Some(meta.modified),
+            };
+            if let Err(e) = self.sftp.setstat(Path::new(&temp), stat) {
+                eprintln!("Impossibile impostare la data di modifica di {}: {}", path, e);
+            }
+        }
+        self.replace(&temp, &target)?;
+        Ok(written)
+    }
+
+    fn get(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(self.sftp.open(Path::new(&self.remote(path)))?))
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<ObjectInfo>> {
+        let folder = self.remote(prefix);
+        let folder = folder.trim_end_matches('/');
+        let mut files = vec![];
+        match self.sftp.stat(Path::new(folder)) {
+            Ok(stat) if stat.is_dir() => self.list_folder(folder, &mut files)?,
+            _ => {}
+        }
+        Ok(files)
+    }
+
+    fn stat(&self, path: &str) -> io::Result<Option<ObjectInfo>> {
+        match self.sftp.stat(Path::new(&self.remote(path))) {
+            Ok(stat) if stat.is_file() => Ok(Some(ObjectInfo {
+                path: path.to_string(),
+                size: stat.size.unwrap_or(0),
+                modified: stat.mtime.unwrap_or(0),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                let e = io::Error::from(e);
+                if e.kind() == io::ErrorKind::NotFound {
+                    Ok(None)
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    fn delete(&self, path: &str) -> io::Result<()> {
+        self.sftp.unlink(Path::new(&self.remote(path)))?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let target = self.remote(to);
+        self.create_parent(&target)?;
+        self.replace(&self.remote(from), &target)
+    }
+}
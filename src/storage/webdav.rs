@@ -0,0 +1,398 @@
+use super::xml::{strip_namespaces, xml_unescape, xml_value, xml_values};
+use super::{FileMeta, ObjectInfo, StorageBackend};
+use crate::utils::WebDavConfig;
+use base64::Engine;
+use chrono::DateTime;
+use digest_auth::{AuthContext, HttpMethod, WwwAuthenticateHeader};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/></d:prop></d:propfind>"#;
+
+/// Codifica un percorso per l'URL, mantenendo i separatori '/'
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn http_error(err: ureq::Error) -> io::Error {
+    match err {
+        ureq::Error::Status(404, _) => io::Error::new(io::ErrorKind::NotFound, "resource not found"),
+        ureq::Error::Status(401, _) | ureq::Error::Status(403, _) => {
+            io::Error::new(io::ErrorKind::PermissionDenied, "authentication failed")
+        }
+        ureq::Error::Status(code, response) => io::Error::new(
+            io::ErrorKind::Other,
+            format!("HTTP {} {}", code, response.status_text()),
+        ),
+        ureq::Error::Transport(transport) => io::Error::new(io::ErrorKind::Other, transport.to_string()),
+    }
+}
+
+/// Lettore che calcola l'hash dei dati mentre vengono inviati al server
+struct HashingReader<'a> {
+    inner: &'a mut dyn Read,
+    hasher: Sha256,
+    count: u64,
+}
+
+impl Read for HashingReader<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.hasher.update(&buffer[..read]);
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+/// Destinazione su un server WebDAV (file server, Nextcloud, ...)
+pub struct WebDavStorage {
+    agent: ureq::Agent,
+    base_url: String,  // URL della cartella di destinazione, senza '/' finale
+    base_path: String, // parte "percorso" dell'URL, decodificata, senza '/' finale
+    user: String,
+    password: String,
+    digest: bool,                                  // autenticazione digest invece di basic
+    challenge: Mutex<Option<WwwAuthenticateHeader>>, // ultima richiesta digest ricevuta dal server
+    folders: Mutex<HashSet<String>>,               // cartelle già create in questa sessione
+}
+
+impl WebDavStorage {
+    /// Controlla che il server sia raggiungibile e che la cartella di destinazione esista (creandola se manca)
+    pub fn connect(config: &WebDavConfig) -> Result<Self, String> {
+        let base_url = config.url.trim_end_matches('/').to_string();
+        let address = base_url
+            .split_once("://")
+            .map(|(_, address)| address)
+            .ok_or_else(|| format!("Invalid WebDAV URL `{}`.", config.url))?;
+        let base_path = match address.find('/') {
+            Some(start) => decode_path(&address[start..]),
+            None => String::new(),
+        };
+        let digest = match config.auth.as_str() {
+            "" | "basic" => false,
+            "digest" => true,
+            other => return Err(format!("Unknown WebDAV authentication `{}`: use basic or digest.", other)),
+        };
+
+        let storage = WebDavStorage {
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(15))
+                .timeout_read(Duration::from_secs(120))
+                .timeout_write(Duration::from_secs(120))
+                .build(),
+            base_url,
+            base_path,
+            user: config.user.clone(),
+            password: config.password.clone(),
+            digest,
+            challenge: Mutex::new(None),
+            folders: Mutex::new(HashSet::new()),
+        };
+
+        let fail = |e: io::Error| format!("Unable to connect to WebDAV server {}: {}", config.url, e);
+        match storage.propfind("", "0") {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                storage
+                    .call("MKCOL", &storage.url(""), &[], &[])
+                    .map_err(|e| fail(http_error(e)))?;
+            }
+            Err(e) => return Err(fail(e)),
+        }
+        Ok(storage)
+    }
+
+    fn url(&self, path: &str) -> String {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            format!("{}/", self.base_url)
+        } else {
+            format!("{}/{}", self.base_url, encode_path(path))
+        }
+    }
+
+    /// Valore dell'header Authorization per una richiesta
+    fn authorization(&self, method: &str, url: &str) -> Option<String> {
+        if self.user.is_empty() {
+            return None;
+        }
+        if !self.digest {
+            let credentials = format!("{}:{}", self.user, self.password);
+            return Some(format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            ));
+        }
+        // Il digest usa il percorso della richiesta, senza schema e host
+        let uri = url
+            .split_once("://")
+            .and_then(|(_, address)| address.find('/').map(|start| &address[start..]))
+            .unwrap_or("/");
+        let mut challenge = self.challenge.lock().unwrap();
+        let context = AuthContext::new_with_method(
+            self.user.as_str(),
+            self.password.as_str(),
+            uri,
+            Option::<&[u8]>::None,
+            HttpMethod::from(method),
+        );
+        challenge
+            .as_mut()
+            .and_then(|header| header.respond(&context).ok())
+            .map(|header| header.to_string())
+    }
+
+    /// Memorizza la richiesta digest del server; restituisce false se non ce n'è una valida
+    fn store_challenge(&self, response: &ureq::Response) -> bool {
+        let header = response
+            .all("WWW-Authenticate")
+            .into_iter()
+            .find(|value| value.trim_start().to_lowercase().starts_with("digest"))
+            .and_then(|value| digest_auth::parse(value).ok());
+        let found = header.is_some();
+        *self.challenge.lock().unwrap() = header;
+        found
+    }
+
+    /// Invia una richiesta; con il digest la ripete una volta dopo aver ricevuto la richiesta del server
+    fn call(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, String)],
+        body: &[u8],
+    ) -> Result<ureq::Response, ureq::Error> {
+        let mut retried = false;
+        loop {
+            let mut request = self.agent.request(method, url);
+            if let Some(authorization) = self.authorization(method, url) {
+                request = request.set("Authorization", &authorization);
+            }
+            for (name, value) in headers {
+                request = request.set(name, value);
+            }
+            let result = if body.is_empty() && !matches!(method, "PUT" | "PROPFIND") {
+                request.call()
+            } else {
+                request.send_bytes(body)
+            };
+            match result {
+                Err(ureq::Error::Status(401, response))
+                    if self.digest && !retried && self.store_challenge(&response) =>
+                {
+                    retried = true;
+                }
+                other => return other,
+            }
+        }
+    }
+
+    /// PROPFIND sulla risorsa indicata; restituisce le risposte con i namespace rimossi
+    fn propfind(&self, path: &str, depth: &str) -> io::Result<Vec<String>> {
+        let headers = [
+            ("Depth", depth.to_string()),
+            ("Content-Type", "application/xml; charset=utf-8".to_string()),
+        ];
+        let body = self
+            .call("PROPFIND", &self.url(path), &headers, PROPFIND_BODY.as_bytes())
+            .map_err(http_error)?
+            .into_string()?;
+        let body = strip_namespaces(&body);
+        Ok(xml_values(&body, "response")
+            .into_iter()
+            .map(|response| response.to_string())
+            .collect())
+    }
+
+    /// Percorso relativo alla destinazione di un `href` restituito dal server
+    fn relative_href(&self, href: &str) -> Option<String> {
+        let href = xml_unescape(href);
+        // Alcuni server restituiscono l'URL completo, altri solo il percorso
+        let path = match href.split_once("://") {
+            Some((_, address)) => address.find('/').map(|start| &address[start..]).unwrap_or("/"),
+            None => href.as_str(),
+        };
+        let path = decode_path(path);
+        let path = path.trim_end_matches('/');
+        let relative = path.strip_prefix(self.base_path.as_str())?;
+        Some(relative.trim_start_matches('/').to_string())
+    }
+
+    fn object_info(&self, response: &str) -> Option<ObjectInfo> {
+        if response.contains("<collection/>") || response.contains("<collection>") {
+            return None;
+        }
+        Some(ObjectInfo {
+            path: self.relative_href(xml_value(response, "href")?)?,
+            size: xml_value(response, "getcontentlength")
+                .and_then(|size| size.trim().parse().ok())
+                .unwrap_or(0),
+            modified: xml_value(response, "getlastmodified")
+                .and_then(|date| DateTime::parse_from_rfc2822(date.trim()).ok())
+                .map(|date| date.timestamp().max(0) as u64)
+                .unwrap_or(0),
+        })
+    }
+
+    fn list_folder(&self, folder: &str, files: &mut Vec<ObjectInfo>) -> io::Result<()> {
+        for response in self.propfind(folder, "1")? {
+            match self.object_info(&response) {
+                Some(info) => files.push(info),
+                None => {
+                    // Sottocartella: la prima risposta è la cartella stessa e va saltata
+                    let subfolder = xml_value(&response, "href").and_then(|href| self.relative_href(href));
+                    let Some(subfolder) = subfolder else {
+                        continue;
+                    };
+                    if subfolder != folder.trim_matches('/') {
+                        self.list_folder(&subfolder, files)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Crea con MKCOL le cartelle mancanti che contengono `path`
+    fn create_parent(&self, path: &str) -> io::Result<()> {
+        let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        for i in 1..components.len() {
+            let folder = components[..i].join("/");
+            if self.folders.lock().unwrap().contains(&folder) {
+                continue;
+            }
+            match self.call("MKCOL", &self.url(&folder), &[], &[]) {
+                // 405: la cartella esiste già
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(e) => return Err(http_error(e)),
+            }
+            self.folders.lock().unwrap().insert(folder);
+        }
+        Ok(())
+    }
+
+    fn move_resource(&self, from: &str, to: &str) -> io::Result<()> {
+        let headers = [("Destination", self.url(to)), ("Overwrite", "T".to_string())];
+        self.call("MOVE", &self.url(from), &headers, &[])
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+impl StorageBackend for WebDavStorage {
+    fn describe(&self) -> String {
+        self.base_url.clone()
+    }
+
+    fn put(&self, path: &str, data: &mut dyn Read, meta: &FileMeta) -> io::Result<u64> {
+        self.create_parent(path)?;
+
+        // I dati vengono inviati in streaming: per il digest serve una richiesta del server già valida
+        if self.digest && self.challenge.lock().unwrap().is_none() {
+            self.propfind("", "0")?;
+        }
+
+        // Come per le cartelle locali: si scrive su un file temporaneo e lo si sposta solo se la copia è integra
+        let temp = format!("{}.g24tmp", path);
+        let url = self.url(&temp);
+        let mut reader = HashingReader {
+            inner: data,
+            hasher: Sha256::new(),
+            count: 0,
+        };
+        let mut request = self
+            .agent
+            .request("PUT", &url)
+            .set("Content-Length", &meta.size.to_string());
+        if let Some(authorization) = self.authorization("PUT", &url) {
+            request = request.set("Authorization", &authorization);
+        }
+        if meta.modified > 0 {
+            // Nextcloud e ownCloud conservano la data di modifica indicata in questo header
+            request = request.set("X-OC-Mtime", &meta.modified.to_string());
+        }
+        request.send(&mut reader).map_err(http_error)?;
+
+        if let Some(expected) = &meta.hash {
+            if format!("{:x}", reader.hasher.finalize()) != *expected {
+                let _ = self.delete(&temp);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Copy verification failed for `{}`.", path),
+                ));
+            }
+        }
+        self.move_resource(&temp, path)?;
+        Ok(reader.count)
+    }
+
+    fn get(&self, path: &str) -> io::Result<Box<dyn Read + Send>> {
+        let response = self.call("GET", &self.url(path), &[], &[]).map_err(http_error)?;
+        Ok(response.into_reader())
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<ObjectInfo>> {
+        let mut files = vec![];
+        match self.list_folder(prefix.trim_matches('/'), &mut files) {
+            Ok(()) => Ok(files),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn stat(&self, path: &str) -> io::Result<Option<ObjectInfo>> {
+        match self.propfind(path, "0") {
+            Ok(responses) => Ok(responses
+                .first()
+                .and_then(|response| self.object_info(response))
+                .map(|info| ObjectInfo {
+                    path: path.to_string(),
+                    ..info
+                })),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn delete(&self, path: &str) -> io::Result<()> {
+        self.call("DELETE", &self.url(path), &[], &[])
+            .map_err(http_error)?;
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.create_parent(to)?;
+        self.move_resource(from, to)
+    }
+}
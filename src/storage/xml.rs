@@ -0,0 +1,72 @@
+// Lettura minimale delle risposte XML dei server (S3, WebDAV): bastano i valori di pochi elementi
+
+/// Valori di tutti gli elementi `<tag>` di una risposta XML
+pub fn xml_values<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut values = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        match rest.find(&close) {
+            Some(end) => {
+                values.push(&rest[..end]);
+                rest = &rest[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    values
+}
+
+pub fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    xml_values(xml, tag).into_iter().next()
+}
+
+pub fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Rimuove i prefissi dei namespace e gli attributi dai tag (`<d:href xmlns:d="DAV:">` diventa `<href>`),
+/// così le risposte dei diversi server si leggono allo stesso modo
+pub fn strip_namespaces(xml: &str) -> String {
+    let mut result = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue; // dichiarazione XML o commento
+        }
+        let closing = tag.starts_with('/');
+        let self_closing = tag.ends_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .trim_end_matches('/')
+            .split_whitespace()
+            .next()
+            .unwrap_or("");
+        let name = name.rsplit(':').next().unwrap_or(name);
+        result.push('<');
+        if closing {
+            result.push('/');
+        }
+        result.push_str(name);
+        if self_closing {
+            result.push('/');
+        }
+        result.push('>');
+    }
+    result.push_str(rest);
+    result
+}
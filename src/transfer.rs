@@ -1,49 +1,140 @@
-use crate::analytics::log_backup_data_to_csv;
-use crate::ui::MyApp;
-use crate::utils::manage_configuration_file;
-use crate::utils::play_sound;
+use crate::analytics::{log_backup_data_to_csv, log_guard_event_to_csv};
+use crate::catalog;
+use crate::guard;
+use crate::manifest::{self, ManifestEntry, RunManifest};
+use crate::parity;
+use crate::storage::{self, FileMeta, StorageBackend};
+use crate::ui::{BackupStatus, MyApp};
+use crate::utils::{load_config, manage_configuration_file, Config};
+use crate::utils::{play_sound, speak};
 use crate::utils::Configuration;
-use sha2::{Digest, Sha256};
-use std::fs;
-use std::fs::File;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File};
 use std::io::{self, Read};
-use std::path::Path;
-use std::sync::mpsc::Receiver;
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
 use systemstat::{Platform, System};
 
+/// Tempo concesso per confermare le modifiche sospette quando il backup è partito da solo
+/// (programmato, per inattività, ...): nessuno potrebbe essere davanti allo schermo
+const UNATTENDED_OVERRIDE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Riepilogo di un backup completato, mostrato nella finestra di fine backup
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct BackupReport {
+    pub run_id: String,
+    pub destination: String,
+    pub fallback: Option<String>, // destinazione di riserva usata perché la principale non era raggiungibile
+    pub files_copied: u64,
+    pub files_unchanged: u64, // file già presenti e identici nella destinazione, non ritrasferiti
+    pub bytes_copied: u64,
+    pub duration_secs: u64,
+    pub parity_bytes: u64,
+    pub recoverable_percentage: Option<f32>, // presente solo se i dati di recupero sono attivi
+}
+
+impl BackupReport {
+    /// Righe di testo da mostrare all'utente
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Backup ID: {}", self.run_id),
+            format!("Destination: {}", self.destination),
+            format!("Files copied: {}", self.files_copied),
+            format!("Data copied: {} bytes", self.bytes_copied),
+            format!("Duration: {}s", self.duration_secs),
+        ];
+        if let Some(fallback) = &self.fallback {
+            lines.push(format!("Primary destination unavailable: used {}", fallback));
+        }
+        if self.files_unchanged > 0 {
+            lines.push(format!("Unchanged files (not transferred): {}", self.files_unchanged));
+        }
+        if let Some(recoverable) = self.recoverable_percentage {
+            let overhead = if self.bytes_copied > 0 {
+                self.parity_bytes as f64 / self.bytes_copied as f64 * 100.0
+            } else {
+                0.0
+            };
+            lines.push(format!(
+                "Parity overhead: {} bytes ({:.1}%)",
+                self.parity_bytes, overhead
+            ));
+            lines.push(format!("Recoverable: up to {:.1}% of each block", recoverable));
+        }
+        lines
+    }
+}
+
+/// Dati raccolti durante la visita dell'albero sorgente
+struct BackupRun {
+    source_root: PathBuf,
+    backend: Box<dyn StorageBackend>, // destinazione scelta nella configurazione
+    parity_redundancy: Option<u8>, // None se i dati di recupero sono disattivati
+    manifest: RunManifest,
+    parity_bytes: u64,
+    latest: HashMap<String, ManifestEntry>, // versione di ogni file attualmente nella destinazione
+    unchanged: HashMap<String, String>, // percorso -> hash dei file già salvati e ancora presenti nella destinazione
+    files_unchanged: u64,
+}
+
+/// `unattended` è vero per i backup avviati senza il gesto, che non possono restare
+/// in attesa di una conferma dell'utente
 pub fn perform_backup_with_stop(
     stop_rx: &Receiver<String>,
     state: &mut MyApp,
-) -> Result<(), String> {
+    unattended: bool,
+) -> Result<BackupReport, String> {
     // Recupera i dati dalla configurazione statica
     let config = manage_configuration_file();
 
     // Verifica se config è di tipo Configuration::Build
-    if let Configuration::Build(source_folder, destination_folder, backup_type, file_types, _) = config
+    if let Configuration::Build(source_folder, _, backup_type, file_types, _) = config
     {
         let source_path = Path::new(&source_folder);
-        let dest_path = Path::new(&destination_folder);
 
-        // Verifica che le cartelle esistano
+        // Verifica che la sorgente esista
         if !source_path.is_dir() {
             return Err(format!(
                 "Source folder: `{}` does not exist.",
                 source_folder
             ));
         }
-        if !dest_path.is_dir() {
-            return Err(format!(
-                "Destination folder: `{}` does not exist.",
-                destination_folder
-            ));
-        }
 
         // Determina i tipi di file da includere
         let include_all =
             backup_type == "total" || (backup_type == "custom" && file_types.is_empty());
         let file_types: Vec<&str> = file_types.iter().map(|s| s.as_str()).collect();
 
+        let config_extras = load_config().unwrap_or_default();
+
+        // Apre la destinazione del tipo scelto nella configurazione (cartella locale, ...)
+        // o, se non è raggiungibile, la prima destinazione di riserva disponibile
+        let destination = match storage::open_with_fallback(&config_extras) {
+            Ok(destination) => destination,
+            Err(e) => {
+                play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+                return Err(e);
+            }
+        };
+        if destination.tier > 0 {
+            play_sound("Sounds/system-notification-199277.mp3");
+            speak(&format!("Primary destination unavailable. Backing up to {}.", destination.name));
+        }
+        let backend = destination.backend;
+        let destination_name = backend.describe();
+
+        // Prima di sovrascrivere le copie, controlla che la sorgente non sia stata alterata in massa
+        if config_extras.guard.enabled {
+            let include = |path: &Path| include_all || matches_file_type(path, &file_types);
+            match guard::check_mass_change(source_path, backend.as_ref(), &include, &config_extras.guard) {
+                Ok(Some(warning)) => wait_for_override(stop_rx, state, warning, unattended)?,
+                Ok(None) => {}
+                Err(e) => eprintln!("Controllo delle modifiche di massa non riuscito: {}", e),
+            }
+        }
+
         // Calcola la durata del backup
         let start_time = Instant::now();
 
@@ -55,10 +146,31 @@ pub fn perform_backup_with_stop(
 
         let mut files_copied = 0;
         let mut total_copied_size = 0;
+        let parity_config = config_extras.parity.clone();
+        let latest = manifest::latest_entries(backend.as_ref()).unwrap_or_default();
+        let unchanged = unchanged_files(backend.as_ref(), &latest);
+        let mut run = BackupRun {
+            source_root: source_path.to_path_buf(),
+            backend,
+            parity_redundancy: if parity_config.enabled {
+                Some(parity_config.redundancy)
+            } else {
+                None
+            },
+            manifest: RunManifest {
+                run_id: manifest::new_run_id(),
+                source_folder: source_folder.clone(),
+                files: vec![],
+                partial: false,
+            },
+            parity_bytes: 0,
+            latest,
+            unchanged,
+            files_unchanged: 0,
+        };
         // Esegui il backup
         if let Err(e) = backup_folder_with_stop(
             source_path,
-            dest_path,
             include_all,
             &file_types,
             stop_rx,
@@ -66,20 +178,50 @@ pub fn perform_backup_with_stop(
             &mut files_copied,
             state,
             &mut total_copied_size,
+            &mut run,
         ) {
+            // Interruzione chiesta dall'utente (pulsante Stop, ritorno durante un backup per
+            // inattività): non è un errore e non va mostrata come tale
+            if e.kind() == io::ErrorKind::Interrupted {
+                return Err("stop".to_string());
+            }
             play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
             return Err(format!("Backup failed: {}", e));
-        } else {
-            play_sound("Sounds/bellding-254774.mp3");
-            let duration = start_time.elapsed().as_secs(); // Durata del backup in secondi
+        }
 
-            // let total_size = get_total_size(source_path).map_err(|e| e.to_string())?; // Calcola i dati trasferiti in byte
-            // Registra i dettagli del backup nelle analitiche
-            let cpu_usage = get_cpu_usage();
-            log_backup_data_to_csv(total_copied_size, duration, cpu_usage);
+        // Registra l'elenco dei file salvati, necessario per verifiche e riparazioni
+        if let Err(e) = manifest::write_manifest(run.backend.as_ref(), &run.manifest) {
+            play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+            return Err(format!("Backup failed: unable to write the backup manifest: {}", e));
+        }
+        // Il catalogo serve solo per la ricerca: un errore non invalida il backup
+        if let Err(e) = catalog::record_run(&run.backend.describe(), &run.manifest) {
+            eprintln!("Impossibile aggiornare il catalogo dei backup: {}", e);
         }
 
-        Ok(())
+        play_sound("Sounds/bellding-254774.mp3");
+        let duration = start_time.elapsed().as_secs(); // Durata del backup in secondi
+
+        // let total_size = get_total_size(source_path).map_err(|e| e.to_string())?; // Calcola i dati trasferiti in byte
+        // Registra i dettagli del backup nelle analitiche
+        let cpu_usage = get_cpu_usage();
+        log_backup_data_to_csv(total_copied_size, duration, cpu_usage);
+
+        Ok(BackupReport {
+            run_id: run.manifest.run_id.clone(),
+            destination: destination_name,
+            fallback: if destination.tier > 0 {
+                Some(destination.name)
+            } else {
+                None
+            },
+            files_copied: files_copied as u64,
+            files_unchanged: run.files_unchanged,
+            bytes_copied: total_copied_size,
+            duration_secs: duration,
+            parity_bytes: run.parity_bytes,
+            recoverable_percentage: run.parity_redundancy.map(parity::recoverable_percentage),
+        })
     } else {
         Err(
             "Configurazione non valida. Imposta una configurazione valida dal pannello di Backup!"
@@ -88,9 +230,75 @@ pub fn perform_backup_with_stop(
     }
 }
 
+/// Mette in pausa il backup finché l'utente non conferma esplicitamente di voler proseguire.
+/// Un backup automatico senza conferma entro `UNATTENDED_OVERRIDE_TIMEOUT` viene annullato.
+fn wait_for_override(
+    stop_rx: &Receiver<String>,
+    state: &mut MyApp,
+    warning: String,
+    unattended: bool,
+) -> Result<(), String> {
+    // Scarta eventuali comandi rimasti nel canale da backup precedenti
+    while stop_rx.try_recv().is_ok() {}
+
+    play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+    {
+        let mut app_state = state.state.lock().unwrap();
+        app_state.backup_status = BackupStatus::ToOverride(warning.clone());
+    }
+    println!("Modifiche sospette nella sorgente: in attesa della conferma dell'utente.");
+
+    let deadline = Instant::now() + UNATTENDED_OVERRIDE_TIMEOUT;
+    loop {
+        let message = if unattended {
+            match stop_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Err(RecvTimeoutError::Timeout) => {
+                    println!("Nessuna conferma per le modifiche sospette: backup automatico annullato.");
+                    log_guard_event_to_csv("aborted", &warning);
+                    return Err(format!("Automatic backup canceled, nobody confirmed it. {}", warning));
+                }
+                message => message.map_err(|_| ()),
+            }
+        } else {
+            stop_rx.recv().map_err(|_| ())
+        };
+        match message.as_deref() {
+            Ok("override") => {
+                let mut app_state = state.state.lock().unwrap();
+                app_state.backup_status = BackupStatus::InProgress;
+                println!("Avviso ignorato dall'utente: il backup prosegue.");
+                return Ok(());
+            }
+            Ok("stop") => return Err("stop".to_string()),
+            Ok(_) => continue,
+            Err(_) => return Err("Backup canceled: unable to receive the user's decision.".to_string()),
+        }
+    }
+}
+
+/// Sospende il backup (richiesta del trigger di inattività) finché non arriva "resume" o "stop"
+fn wait_while_paused(stop_rx: &Receiver<String>, state: &mut MyApp) -> io::Result<()> {
+    state.state.lock().unwrap().backup_paused = true;
+    println!("Backup in pausa.");
+    let result = loop {
+        match stop_rx.recv().as_deref() {
+            Ok("resume") => break Ok(()),
+            Ok("stop") | Err(_) => {
+                play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
+                break Err(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Backup interrotto dall'utente.",
+                ));
+            }
+            Ok(_) => continue,
+        }
+    };
+    state.state.lock().unwrap().backup_paused = false;
+    result
+}
+
 fn backup_folder_with_stop(
     source: &Path,
-    destination: &Path,
     include_all: bool,
     file_types: &[&str],
     stop_rx: &Receiver<String>,
@@ -98,17 +306,15 @@ fn backup_folder_with_stop(
     files_copied: &mut i32,
     state: &mut MyApp,
     total_copied_size: &mut u64,
+    run: &mut BackupRun,
 ) -> io::Result<()> {
-    // Crea la directory di destinazione se non esiste
-    if !destination.exists() {
-        fs::create_dir_all(destination)?;
-    }
-
     // Itera sui file e sottocartelle nella sorgente
     for entry in fs::read_dir(source)? {
         // Controlla se è stato ricevuto il comando di stop
         if let Ok(msg) = stop_rx.try_recv() {
-            if msg == "stop" {
+            if msg == "pause" {
+                wait_while_paused(stop_rx, state)?;
+            } else if msg == "stop" {
                 play_sound("Sounds/incorrect-buzzer-sound-147336.mp3");
                 return Err(io::Error::new(
                     io::ErrorKind::Interrupted,
@@ -119,7 +325,6 @@ fn backup_folder_with_stop(
 
         let entry = entry?;
         let path = entry.path();
-        let dest_path = destination.join(entry.file_name());
 
         println!("Processing: {:?}", path);
         if path.is_dir() {
@@ -127,7 +332,6 @@ fn backup_folder_with_stop(
             // Esegui il backup ricorsivamente per le sottocartelle
             backup_folder_with_stop(
                 &path,
-                &dest_path,
                 include_all,
                 file_types,
                 stop_rx,
@@ -135,20 +339,49 @@ fn backup_folder_with_stop(
                 files_copied,
                 state,
                 total_copied_size,
+                run,
             )?;
         } else if path.is_file() {
             // Copia il file se rientra nei criteri
             if include_all || matches_file_type(&path, file_types) {
-                println!("Copying file: {:?} -> {:?}", path, dest_path);
+                let relative = manifest::relative_path(&run.source_root, &path);
+                println!("Copying file: {:?} -> {}", path, relative);
                 {
                     let mut current_file = state.current_file.lock().unwrap();
                     *current_file = Some(path.to_string_lossy().to_string()); // Salva il percorso completo
                 }
-                fs::copy(&path, &dest_path)?;
 
-                // Aggiorna la dimensione totale dei dati copiati
-                let file_size = path.metadata()?.len();
-                *total_copied_size += file_size;
+                // L'hash della sorgente viene passato alla destinazione, che verifica la copia prima di confermarla
+                let metadata = path.metadata()?;
+                let hash = manifest::hash_file(&path)?;
+                let meta = FileMeta {
+                    size: metadata.len(),
+                    modified: manifest::modified_secs(&metadata),
+                    hash: Some(hash.clone()),
+                };
+                let file_size = if run.unchanged.get(&relative) == Some(&hash) {
+                    // Contenuto identico a quello già salvato: non serve trasferirlo di nuovo
+                    println!("Unchanged, skipping transfer: {}", relative);
+                    run.files_unchanged += 1;
+                    meta.size
+                } else {
+                    let previous = run.latest.get(&relative).map(|entry| entry.hash.as_str());
+                    let mut file = File::open(&path)?;
+                    let written = put_keeping_previous(run.backend.as_ref(), &relative, &mut file, &meta, previous)?;
+                    // Aggiorna la dimensione totale dei dati copiati
+                    *total_copied_size += written;
+                    written
+                };
+
+                if let Some(redundancy) = run.parity_redundancy {
+                    run.parity_bytes += store_parity(run.backend.as_ref(), &path, &hash, redundancy)?;
+                }
+                run.manifest.files.push(ManifestEntry {
+                    path: relative,
+                    size: file_size,
+                    modified: meta.modified,
+                    hash,
+                });
 
                 // Aggiorna il progresso
                 *files_copied += 1;
@@ -167,6 +400,74 @@ fn backup_folder_with_stop(
     Ok(())
 }
 
+/// Salva un file nella destinazione spostando prima la versione che sostituisce (`previous`, l'hash
+/// registrato dai manifest) in `.group24/objects/<hash>`, così i backup precedenti restano ripristinabili
+pub fn put_keeping_previous(
+    backend: &dyn StorageBackend,
+    relative: &str,
+    data: &mut dyn Read,
+    meta: &FileMeta,
+    previous: Option<&str>,
+) -> io::Result<u64> {
+    let mut moved = None;
+    if let Some(previous) = previous.filter(|previous| Some(*previous) != meta.hash.as_deref()) {
+        let object = manifest::object_path(previous);
+        // Una versione identica può essere già stata conservata (es. un file tornato com'era)
+        if backend.stat(&object)?.is_none() && backend.stat(relative)?.is_some() {
+            backend.rename(relative, &object)?;
+            moved = Some(object);
+        }
+        if let Err(e) = catalog::record_object(&backend.describe(), previous) {
+            eprintln!("Impossibile aggiornare il catalogo dei backup: {}", e);
+        }
+    }
+    let result = backend.put(relative, data, meta);
+    // Copia non riuscita: la versione precedente torna al suo posto
+    if let (Err(_), Some(object)) = (&result, moved) {
+        let _ = backend.rename(&object, relative);
+    }
+    result
+}
+
+/// File dell'ultimo backup ancora presenti nella destinazione con la dimensione registrata.
+/// Un solo elenco della destinazione (es. PROPFIND per WebDAV) evita di interrogarla file per file.
+/// `latest` è il risultato di `manifest::latest_entries`.
+pub fn unchanged_files(
+    backend: &dyn StorageBackend,
+    latest: &HashMap<String, ManifestEntry>,
+) -> HashMap<String, String> {
+    let stored: HashMap<String, u64> = match backend.list("") {
+        Ok(files) => files.into_iter().map(|f| (f.path, f.size)).collect(),
+        Err(e) => {
+            eprintln!("Impossibile elencare i file della destinazione: {}", e);
+            return HashMap::new();
+        }
+    };
+    latest
+        .iter()
+        .filter(|(path, entry)| stored.get(*path) == Some(&entry.size))
+        .map(|(path, entry)| (path.clone(), entry.hash.clone()))
+        .collect()
+}
+
+/// Genera i dati di recupero di un file e li salva nella destinazione, se non sono già presenti
+fn store_parity(backend: &dyn StorageBackend, file: &Path, hash: &str, redundancy: u8) -> io::Result<u64> {
+    let parity_file = parity::parity_path(hash);
+    if let Some(existing) = backend.stat(&parity_file)? {
+        return Ok(existing.size);
+    }
+    let local_parity = parity::temp_path(&format!("{}.par", hash));
+    let result = parity::write_parity(file, &local_parity, redundancy).and_then(|_| {
+        let meta = FileMeta {
+            size: local_parity.metadata()?.len(),
+            ..Default::default()
+        };
+        backend.put(&parity_file, &mut File::open(&local_parity)?, &meta)
+    });
+    let _ = fs::remove_file(&local_parity);
+    result
+}
+
 fn count_files_in_directory(path: &Path) -> io::Result<u64> {
     let mut file_count = 0;
 
@@ -188,6 +489,45 @@ fn count_files_in_directory(path: &Path) -> io::Result<u64> {
     Ok(file_count)
 }
 
+/// Vero se il file rientra nel backup secondo la configurazione (tipo di backup e tipi di file)
+pub fn is_included(config: &Config, path: &Path) -> bool {
+    let include_all = config.backup_type == "total"
+        || (config.backup_type == "custom" && config.file_types.is_empty());
+    let file_types: Vec<&str> = config.file_types.iter().map(|s| s.as_str()).collect();
+    include_all || matches_file_type(path, &file_types)
+}
+
+/// Copia un singolo file della sorgente (con i dati di recupero, se attivi) e restituisce la voce
+/// del manifest; usata dai backup parziali (protezione continua, backup allo spegnimento).
+/// `latest` è il risultato di `manifest::latest_entries`: la versione sostituita viene conservata.
+pub fn copy_single_file(
+    backend: &dyn StorageBackend,
+    config: &Config,
+    path: &Path,
+    latest: &HashMap<String, ManifestEntry>,
+) -> io::Result<ManifestEntry> {
+    let relative = manifest::relative_path(Path::new(&config.source_folder), path);
+    let metadata = path.metadata()?;
+    let hash = manifest::hash_file(path)?;
+    let meta = FileMeta {
+        size: metadata.len(),
+        modified: manifest::modified_secs(&metadata),
+        hash: Some(hash.clone()),
+    };
+    // Se il file cambia durante la copia la verifica dell'hash fallisce e la copia non viene confermata
+    let previous = latest.get(&relative).map(|entry| entry.hash.as_str());
+    put_keeping_previous(backend, &relative, &mut File::open(path)?, &meta, previous)?;
+    if config.parity.enabled {
+        store_parity(backend, path, &hash, config.parity.redundancy)?;
+    }
+    Ok(ManifestEntry {
+        path: relative,
+        size: meta.size,
+        modified: meta.modified,
+        hash,
+    })
+}
+
 /// Controlla se un file corrisponde ai tipi specificati
 fn matches_file_type(file: &Path, file_types: &[&str]) -> bool {
     // Estrazione dell'estensione:
@@ -208,7 +548,7 @@ fn matches_file_type(file: &Path, file_types: &[&str]) -> bool {
     }
 }
 
-fn get_cpu_usage() -> f32 {
+pub fn get_cpu_usage() -> f32 {
     let sys = System::new();
     match sys.cpu_load_aggregate() {
         Ok(cpu) => {
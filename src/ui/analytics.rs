@@ -1,10 +1,12 @@
 use eframe::egui::{self, RichText, Ui, Color32};
 use std::fs;
+use super::AppState;
 
 static mut SHOWN_LOGS_CPU: usize = 5;
 static mut SHOWN_LOGS_BACKUP: usize = 5;
+static mut SHOWN_LOGS_DELAY: usize = 5;
 
-pub fn show_analytics_panel(ui: &mut Ui) {
+pub fn show_analytics_panel(ui: &mut Ui, state: &AppState) {
     // Usa ScrollArea per tutto il contenuto
     egui::ScrollArea::vertical().show(ui, |ui| {
         ui.vertical(|ui| {
@@ -142,6 +144,92 @@ pub fn show_analytics_panel(ui: &mut Ui) {
                 }
             }
         });
+
+        ui.separator();
+
+        // Sezione protezione continua (watcher delle cartelle sorgente)
+        ui.vertical(|ui| {
+            ui.heading(RichText::new("Section 4: Continuous Protection").color(Color32::from_rgb(0x87, 0xCE, 0xFA)));
+
+            let watch = &state.watch;
+            if !watch.active {
+                ui.label("Continuous protection is off (enable it in the [watch] section of config_build.toml).");
+                return;
+            }
+            ui.label(format!("Watching: {}", watch.roots.join(", ")));
+            ui.horizontal(|ui| {
+                ui.label("Last sync:");
+                ui.label(watch.last_sync.as_deref().unwrap_or("never"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Files synced since start:");
+                ui.label(format!("{}", watch.files_synced));
+            });
+            if let Some(error) = &watch.error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            if watch.pending.is_empty() {
+                ui.label("No changes waiting to be copied.");
+            } else {
+                ui.label(format!("Changes waiting to be copied: {}", watch.pending.len()));
+                egui::Grid::new("watch_queue_table")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for path in &watch.pending {
+                            ui.label(path);
+                            ui.end_row();
+                        }
+                    });
+            }
+        });
+
+        ui.separator();
+
+        // Sezione job rimandati in attesa di un carico della CPU basso
+        ui.vertical(|ui| {
+            ui.heading(RichText::new("Section 5: Delayed Jobs").color(Color32::from_rgb(0x87, 0xCE, 0xFA)));
+
+            unsafe {
+                if let Some(delay_entries) = read_job_delay_log("job_delay_log.csv") {
+                    let total_entries = delay_entries.len();
+
+                    egui::Grid::new("job_delay_table")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            // Intestazioni della tabella
+                            ui.label("Timestamp");
+                            ui.label("Job");
+                            ui.label("Delay");
+                            ui.label("Started because");
+                            ui.end_row();
+
+                            // Popola la tabella con i ritardi più recenti
+                            for entry in delay_entries.iter().rev().take(SHOWN_LOGS_DELAY) {
+                                ui.label(&entry.timestamp);
+                                ui.label(&entry.job);
+                                ui.label(format_duration(entry.delay));
+                                ui.label(&entry.reason);
+                                ui.end_row();
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        if SHOWN_LOGS_DELAY < total_entries {
+                            if ui.button("Show more").clicked() {
+                                SHOWN_LOGS_DELAY += 5; // Mostra altri 5 log
+                            }
+                        }
+                        if SHOWN_LOGS_DELAY > 5 {
+                            if ui.button("Restore").clicked() {
+                                SHOWN_LOGS_DELAY = 5; // Ripristina a 5 log
+                            }
+                        }
+                    });
+                } else {
+                    ui.label("No job has been delayed by the CPU load check yet.");
+                }
+            }
+        });
     });
 }
 
@@ -197,6 +285,35 @@ fn read_backup_log(file_path: &str) -> Option<Vec<BackupLogEntry>> {
     }
 }
 
+// Funzione per leggere i ritardi dal file `job_delay_log.csv`
+fn read_job_delay_log(file_path: &str) -> Option<Vec<JobDelayEntry>> {
+    let data = fs::read_to_string(file_path).ok()?;
+    let mut entries = vec![];
+    for line in data.lines().skip(1) { // Salta l'intestazione
+        let fields: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+        if fields.len() == 4 {
+            if let Ok(delay) = fields[2].parse::<u64>() {
+                entries.push(JobDelayEntry {
+                    timestamp: fields[0].to_string(),
+                    job: fields[1].to_string(),
+                    delay,
+                    reason: fields[3].to_string(),
+                });
+            }
+        }
+    }
+    Some(entries)
+}
+
+// Struttura per i ritardi dei job
+#[derive(Debug)]
+struct JobDelayEntry {
+    timestamp: String,
+    job: String,
+    delay: u64,     // Ritardo in secondi
+    reason: String, // Carico basso o attesa massima raggiunta
+}
+
 // Struttura per i log di backup
 #[derive(Debug)]
 struct BackupLogEntry {
@@ -206,7 +323,7 @@ struct BackupLogEntry {
     cpu_usage: f32,     // Utilizzo della CPU in percentuale
 }
 
-fn format_data_size(bytes: u64) -> String {
+pub fn format_data_size(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = 1024 * KB;
     const GB: u64 = 1024 * MB;
@@ -1,18 +1,9 @@
 use eframe::egui;
-use serde::Serialize;
-use crate::utils::manage_configuration_file;
+use crate::queue;
+use crate::utils::{load_config, manage_configuration_file, store_config};
+use crate::volumes;
 
 use super::{AppState, ErrorSource, InfoSource};
-use toml;
-
-#[derive(Serialize)]
-pub struct ConfigToSave {
-    pub source_folder: String,
-    pub destination_folder: String,
-    pub backup_type: String,
-    pub file_types: Vec<String>,
-    pub display: bool
-}
 
 const MAX_FILE_TYPES: usize = 10;
 const MAX_EXTENSION_LENGTH: usize = 6; // Including the dot
@@ -36,18 +27,36 @@ pub fn show_backup_panel(ui: &mut egui::Ui, state: &mut AppState) {
     ui.separator(); // Divider between rows
 
     // 2nd row: Select destination folder
-    ui.horizontal(|ui| {
-        ui.label("Select destination folder:");
-        if ui.button("Choose").clicked() {
-            if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                state.destination_folder = path.to_string_lossy().to_string();
+    if let Some(remote) = &state.remote_destination {
+        // Le destinazioni remote si impostano nel file di configurazione
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Remote destination:");
+            ui.add(egui::Label::new(remote).wrap(true));
+        });
+        ui.label("(configured in config_build.toml)");
+    } else {
+        ui.horizontal(|ui| {
+            ui.label("Destination:");
+            ui.radio_value(&mut state.use_volume, false, "Folder");
+            ui.radio_value(&mut state.use_volume, true, "Removable volume");
+        });
+    }
+    if state.remote_destination.is_none() && state.use_volume {
+        show_volume_choice(ui, state);
+    } else if state.remote_destination.is_none() {
+        ui.horizontal(|ui| {
+            ui.label("Select destination folder:");
+            if ui.button("Choose").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    state.destination_folder = path.to_string_lossy().to_string();
+                }
             }
-        }
-    });
-    ui.horizontal_wrapped(|ui| {
-        ui.label("Chosen:");
-        ui.add(egui::Label::new(&state.destination_folder).wrap(true));
-    });
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Chosen:");
+            ui.add(egui::Label::new(&state.destination_folder).wrap(true));
+        });
+    }
 
     ui.separator();
 
@@ -132,6 +141,54 @@ pub fn show_backup_panel(ui: &mut egui::Ui, state: &mut AppState) {
 
     ui.separator();
 
+    // Dati di recupero contro il degrado dei supporti (bit-rot)
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut state.parity_enabled, "Generate recovery data (parity)");
+        if state.repair_running {
+            ui.add_enabled(false, egui::Button::new("Repairing..."));
+            // L'esito arriva dal thread della riparazione: si ridisegna finché non termina
+            ui.ctx().request_repaint_after(std::time::Duration::from_millis(500));
+        } else if ui.button("Repair damaged files").clicked() {
+            state.repair_requested = true;
+        }
+        // Copia i backup mancanti sulla destinazione di [replication] (es. disco USB fuori sede)
+        if ui.button("Replicate backups").clicked() {
+            state.replication_requested = true;
+        }
+    });
+
+    ui.separator();
+
+    // Prossime esecuzioni programmate nelle sezioni [[schedule]] di config_build.toml
+    if !state.next_scheduled.is_empty() {
+        ui.label("Next scheduled runs:");
+        for next in &state.next_scheduled {
+            ui.label(next);
+        }
+        ui.separator();
+    }
+
+    // Backup rimandati perché la destinazione non era disponibile
+    if !state.pending_jobs.is_empty() {
+        ui.label(format!("Pending backups (waiting for the destination): {}", state.pending_jobs.len()));
+        let mut removed = None;
+        egui::Grid::new("pending_jobs").striped(true).show(ui, |ui| {
+            for job in &state.pending_jobs {
+                ui.label(&job.queued_at);
+                ui.label(&job.trigger);
+                if ui.button("Remove").clicked() {
+                    removed = Some(job.clone());
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(job) = removed {
+            queue::remove(&job);
+            state.pending_jobs.retain(|pending| *pending != job);
+        }
+        ui.separator();
+    }
+
     // 5th row: Restore and Save buttons
     ui.horizontal(|ui| {
         if ui.button("Restore").clicked() {
@@ -142,7 +199,15 @@ pub fn show_backup_panel(ui: &mut egui::Ui, state: &mut AppState) {
         if ui.button("Save").clicked() {
 
             // Verifica che i percorsi a livello di stringa non siano vuoti
-            if state.source_folder.is_empty() || state.destination_folder.is_empty() {
+            // Con una destinazione remota o su volume la cartella di destinazione non viene usata
+            let remote = state.remote_destination.is_some() || state.use_volume;
+            if state.use_volume && state.volume.uuid.is_empty() && state.volume.label.is_empty() {
+                state.error_message = Some("Please choose the destination volume.".to_string());
+                state.error_source = Some(ErrorSource::SaveOperation);
+                state.show_error_modal = true;
+                return;
+            }
+            if state.source_folder.is_empty() || (!remote && state.destination_folder.is_empty()) {
                 state.error_message = Some("Source or destination folder path cannot be empty.".to_string());
                 state.error_source = Some(ErrorSource::SaveOperation);
                 state.show_error_modal = true;
@@ -150,7 +215,7 @@ pub fn show_backup_panel(ui: &mut egui::Ui, state: &mut AppState) {
             }
 
             // Verifica che le due cartelle siano diverse
-            if state.source_folder == state.destination_folder {
+            if !remote && state.source_folder == state.destination_folder {
                 state.error_message = Some("Source and destination folders cannot be the same.".to_string());
                 state.error_source = Some(ErrorSource::SaveOperation);
                 state.show_error_modal = true;
@@ -173,7 +238,7 @@ pub fn show_backup_panel(ui: &mut egui::Ui, state: &mut AppState) {
             }
 
             // Controlla se la cartella `destination_folder` contiene già dei file
-            if let Ok(entries) = std::fs::read_dir(&state.destination_folder) {
+            if let Some(entries) = std::fs::read_dir(&state.destination_folder).ok().filter(|_| !remote) {
                 if entries.count() > 0 {
                     state.info_message = Some("Destination folder is not empty. Existing files may be overwritten.".to_string());
                     state.info_source = Some(InfoSource::Attention);
@@ -189,41 +254,79 @@ pub fn show_backup_panel(ui: &mut egui::Ui, state: &mut AppState) {
     
 }
 
+/// Scelta di un volume rimovibile come destinazione, riconosciuto dall'UUID anche se cambia punto di montaggio
+fn show_volume_choice(ui: &mut egui::Ui, state: &mut AppState) {
+    ui.horizontal(|ui| {
+        ui.label("Select volume:");
+        let selected = state
+            .volumes
+            .iter()
+            .find(|v| v.uuid == state.volume.uuid && v.label == state.volume.label)
+            .map(|v| v.display_name())
+            .unwrap_or_else(|| {
+                if state.volume.uuid.is_empty() && state.volume.label.is_empty() {
+                    "None".to_string()
+                } else {
+                    format!("{} (not connected)", volumes::describe(&state.volume))
+                }
+            });
+        egui::ComboBox::from_id_source("destination_volume")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for volume in &state.volumes {
+                    let chosen = volume.uuid == state.volume.uuid && volume.label == state.volume.label;
+                    if ui.selectable_label(chosen, volume.display_name()).clicked() {
+                        state.volume.uuid = volume.uuid.clone();
+                        state.volume.label = volume.label.clone();
+                    }
+                }
+            });
+        if ui.button("Refresh").clicked() {
+            state.volumes = volumes::removable_volumes();
+        }
+    });
+    if state.volumes.is_empty() {
+        ui.label("No removable volume connected.");
+    }
+    ui.horizontal(|ui| {
+        ui.label("Folder on the volume:");
+        ui.text_edit_singleline(&mut state.volume.relative_path);
+    });
+    ui.checkbox(&mut state.mount_trigger_enabled, "Start the backup when this volume is connected");
+}
+
 pub fn save_folders(state: &mut AppState){
     // Se il backup scelto è di tipo "total" allora ripulisci file_types
     if state.backup_type == "total" {
         state.file_types.clear();
     }
 
-    // Crea una versione semplificata con i campi che vogliamo serializzare
-    let config_to_save = ConfigToSave {
-        source_folder: state.source_folder.clone(),
-        destination_folder: state.destination_folder.clone(),
-        backup_type: state.backup_type.clone(),
-        file_types: state.file_types.clone(),
-        display: state.run_gui
-    };
+    // Parti dalla configurazione esistente per non perdere le sezioni non gestite da questo pannello
+    let mut config_to_save = load_config().unwrap_or_default();
+    config_to_save.source_folder = state.source_folder.clone();
+    config_to_save.destination_folder = state.destination_folder.clone();
+    config_to_save.backup_type = state.backup_type.clone();
+    config_to_save.file_types = state.file_types.clone();
+    config_to_save.display = state.run_gui;
+    config_to_save.parity.enabled = state.parity_enabled;
+    // Le destinazioni remote si impostano solo nel file di configurazione
+    if state.remote_destination.is_none() {
+        config_to_save.destination_type = if state.use_volume { "volume" } else { "local" }.to_string();
+        config_to_save.volume = state.volume.clone();
+        config_to_save.mount_trigger.enabled = state.use_volume && state.mount_trigger_enabled;
+    }
 
     // Prova a serializzare lo stato in formato TOML e a salvare il file
-    match toml::to_string(&config_to_save) {
-        Ok(config) => {
-            if let Err(e) = std::fs::write("config_build.toml", config) {
-                state.error_message = Some(format!("Failed to save configuration: {}", e));
-                state.error_source = Some(ErrorSource::SaveOperation);
-                state.show_error_modal = true;
-            } else {
-                state.info_message = Some("Configuration saved successfully.".to_string());
-                state.info_source = Some(InfoSource::Success);
-                state.show_info_modal=true;
-                state.error_message = None; // Nessun errore, cancella eventuali messaggi precedenti
-                state.error_source = None;
-                state.show_error_modal = false;
-            }
-        }
-        Err(e) => {
-            state.error_message = Some(format!("Serialization error: {}", e));
-            state.error_source = Some(ErrorSource::SaveOperation);
-            state.show_error_modal = true;
-        }
+    if let Err(e) = store_config(&config_to_save) {
+        state.error_message = Some(format!("Failed to save configuration: {}", e));
+        state.error_source = Some(ErrorSource::SaveOperation);
+        state.show_error_modal = true;
+    } else {
+        state.info_message = Some("Configuration saved successfully.".to_string());
+        state.info_source = Some(InfoSource::Success);
+        state.show_info_modal=true;
+        state.error_message = None; // Nessun errore, cancella eventuali messaggi precedenti
+        state.error_source = None;
+        state.show_error_modal = false;
     }
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+use chrono::{Local, TimeZone};
+use eframe::egui::{self, Color32, RichText};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::analytics::format_data_size;
+use super::AppState;
+use crate::catalog::{self, CatalogEntry};
+use crate::diff;
+use crate::manifest;
+use crate::storage;
+
+/// Operazione del pannello catalogo che usa la destinazione: viene eseguita in un thread separato
+/// perché con una destinazione remota può richiedere tempo
+#[derive(Serialize, Debug)]
+pub enum CatalogRequest {
+    Search,
+    Restore(CatalogEntry, PathBuf), // file da ripristinare e cartella scelta dall'utente
+    ListRuns,
+    Compare(String, String),
+}
+
+/// Mostra il pannello di ricerca nel catalogo dei backup
+pub fn show_catalog_panel(ui: &mut egui::Ui, state: &mut AppState) {
+    ui.heading(RichText::new("Search Backup Catalog").color(Color32::from_rgb(0x87, 0xCE, 0xFA)));
+
+    egui::Grid::new("catalog_search").num_columns(2).show(ui, |ui| {
+        ui.label("File name:");
+        ui.text_edit_singleline(&mut state.catalog_query.name);
+        ui.end_row();
+
+        ui.label("Extension:");
+        ui.text_edit_singleline(&mut state.catalog_query.extension);
+        ui.end_row();
+
+        ui.label("Modified from (YYYY-MM-DD):");
+        ui.text_edit_singleline(&mut state.catalog_query.from);
+        ui.end_row();
+
+        ui.label("Modified to (YYYY-MM-DD):");
+        ui.text_edit_singleline(&mut state.catalog_query.to);
+        ui.end_row();
+    });
+
+    ui.horizontal(|ui| {
+        if ui.add_enabled(!state.catalog_busy, egui::Button::new("Search")).clicked() {
+            state.catalog_request = Some(CatalogRequest::Search);
+        }
+        if state.catalog_busy {
+            ui.spinner();
+            ui.ctx().request_repaint_after(std::time::Duration::from_millis(500));
+        }
+    });
+
+    if let Some(message) = &state.catalog_message {
+        ui.label(message);
+    }
+
+    ui.separator();
+
+    show_run_comparison(ui, state);
+
+    ui.separator();
+
+    let mut restore_request = None;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("catalog_results").striped(true).show(ui, |ui| {
+            ui.label("Backup");
+            ui.label("File");
+            ui.label("Size");
+            ui.label("Modified");
+            ui.label("");
+            ui.end_row();
+
+            for entry in &state.catalog_results {
+                ui.label(&entry.run_id);
+                ui.add(egui::Label::new(&entry.path).wrap(true));
+                ui.label(format_data_size(entry.size));
+                let modified = Local
+                    .timestamp_opt(entry.modified as i64, 0)
+                    .single()
+                    .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                ui.label(modified);
+                let restore = ui
+                    .add_enabled(entry.available && !state.catalog_busy, egui::Button::new("Restore"))
+                    .on_disabled_hover_text("Replaced by a later backup before older versions were kept.");
+                if restore.clicked() {
+                    if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                        restore_request = Some(CatalogRequest::Restore(entry.clone(), folder));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+
+    if restore_request.is_some() {
+        state.catalog_request = restore_request;
+    }
+}
+
+/// Confronto tra due backup della destinazione corrente
+fn show_run_comparison(ui: &mut egui::Ui, state: &mut AppState) {
+    ui.heading(RichText::new("Compare Backups").color(Color32::from_rgb(0x87, 0xCE, 0xFA)));
+
+    // L'elenco viene letto solo su richiesta: una destinazione remota non va interrogata ad ogni frame
+    let refresh = ui.add_enabled(!state.catalog_busy, egui::Button::new("Refresh")).clicked();
+    if (state.diff_runs.is_none() && !state.catalog_busy) || refresh {
+        state.diff_runs = Some(vec![]);
+        state.catalog_request = Some(CatalogRequest::ListRuns);
+    }
+    let runs = state.diff_runs.clone().unwrap_or_default();
+    if runs.len() < 2 {
+        ui.label(if state.catalog_busy {
+            "Loading backups..."
+        } else {
+            "At least two backups are needed to compare them."
+        });
+        return;
+    }
+
+    ui.horizontal(|ui| {
+        egui::ComboBox::from_label("→")
+            .selected_text(state.diff_old_run.clone())
+            .show_ui(ui, |ui| {
+                for run in &runs {
+                    ui.selectable_value(&mut state.diff_old_run, run.clone(), run);
+                }
+            });
+        egui::ComboBox::from_id_source("diff_new_run")
+            .selected_text(state.diff_new_run.clone())
+            .show_ui(ui, |ui| {
+                for run in &runs {
+                    ui.selectable_value(&mut state.diff_new_run, run.clone(), run);
+                }
+            });
+        if ui.add_enabled(!state.catalog_busy, egui::Button::new("Compare")).clicked() {
+            state.catalog_request = Some(CatalogRequest::Compare(
+                state.diff_old_run.clone(),
+                state.diff_new_run.clone(),
+            ));
+        }
+    });
+
+    match &state.run_diff {
+        Some(Ok(result)) => {
+            ui.label(format!(
+                "{} added, {} removed, {} modified, {} renamed ({:+} bytes)",
+                result.added.len(),
+                result.removed.len(),
+                result.modified.len(),
+                result.renamed.len(),
+                result.size_delta()
+            ));
+            egui::ScrollArea::vertical()
+                .id_source("diff_scroll")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("diff_results").striped(true).show(ui, |ui| {
+                        for (path, size) in &result.added {
+                            ui.colored_label(Color32::LIGHT_GREEN, "Added");
+                            ui.label(path);
+                            ui.label(format!("+{}", format_data_size(*size)));
+                            ui.end_row();
+                        }
+                        for (path, size) in &result.removed {
+                            ui.colored_label(Color32::LIGHT_RED, "Removed");
+                            ui.label(path);
+                            ui.label(format!("-{}", format_data_size(*size)));
+                            ui.end_row();
+                        }
+                        for file in &result.modified {
+                            ui.colored_label(Color32::YELLOW, "Modified");
+                            ui.label(&file.path);
+                            ui.label(format!("{:+} bytes", file.new_size as i64 - file.old_size as i64));
+                            ui.end_row();
+                        }
+                        for file in &result.renamed {
+                            ui.colored_label(Color32::LIGHT_BLUE, "Renamed");
+                            ui.label(format!("{} → {}", file.from, file.to));
+                            ui.label(format_data_size(file.size));
+                            ui.end_row();
+                        }
+                    });
+                });
+        }
+        Some(Err(e)) => {
+            ui.colored_label(Color32::LIGHT_RED, e);
+        }
+        None => {}
+    }
+}
+
+/// Esegue in un thread separato l'operazione richiesta dal pannello e ne riporta l'esito nello stato
+pub(super) fn start_request(
+    app_state: &mut AppState,
+    request: CatalogRequest,
+    shared_state: Arc<Mutex<AppState>>,
+) {
+    if app_state.catalog_busy {
+        return;
+    }
+    app_state.catalog_busy = true;
+    let query = app_state.catalog_query.clone();
+    thread::spawn(move || match request {
+        CatalogRequest::Search => {
+            // Importa eventuali backup della destinazione corrente non ancora catalogati
+            if let Ok(backend) = storage::open_configured() {
+                if let Err(e) = catalog::sync_destination(backend.as_ref()) {
+                    eprintln!("Impossibile sincronizzare il catalogo: {}", e);
+                }
+            }
+            let results = catalog::search(&query);
+            let mut app_state = shared_state.lock().unwrap();
+            app_state.catalog_busy = false;
+            match results {
+                Ok(results) => {
+                    app_state.catalog_message = Some(format!("{} file(s) found.", results.len()));
+                    app_state.catalog_results = results;
+                }
+                Err(e) => {
+                    app_state.catalog_message = Some(e);
+                    app_state.catalog_results.clear();
+                }
+            }
+        }
+        CatalogRequest::Restore(entry, folder) => {
+            let message = match catalog::restore(&entry, &folder) {
+                Ok(()) => format!("`{}` restored to {}.", entry.path, folder.display()),
+                Err(e) => e,
+            };
+            let mut app_state = shared_state.lock().unwrap();
+            app_state.catalog_busy = false;
+            app_state.catalog_message = Some(message);
+        }
+        CatalogRequest::ListRuns => {
+            let runs = match storage::open_configured() {
+                Ok(backend) => manifest::list_runs(backend.as_ref()),
+                Err(_) => vec![],
+            };
+            let mut app_state = shared_state.lock().unwrap();
+            app_state.catalog_busy = false;
+            app_state.diff_runs = Some(runs);
+        }
+        CatalogRequest::Compare(old_run, new_run) => {
+            let diff = storage::open_configured()
+                .and_then(|backend| diff::diff_stored_runs(backend.as_ref(), &old_run, &new_run));
+            let mut app_state = shared_state.lock().unwrap();
+            app_state.catalog_busy = false;
+            app_state.run_diff = Some(diff);
+        }
+    });
+}
@@ -58,6 +58,12 @@ pub fn show_info_panel(ui: &mut egui::Ui, state: &mut AppState) {
         ui.strong("Second Command to Start the Backup");
         ui.label("Next, draw a horizontal line with your mouse. Upon completion, you will hear another confirmation sound, indicating that the backup process has been activated.");
 
+        ui.strong("Aborting an Accidental Backup");
+        ui.label("After the confirmation you will hear a short beep every second before the backup starts. During this countdown, right-click or press Escape to abort.");
+
+        ui.strong("Keyboard Alternative");
+        ui.label("If tracing the screen border is not possible, hold Ctrl+Alt+Shift+B for two seconds instead, then confirm by holding Ctrl+Alt+Shift+Y. Both key chords can be changed in the [hotkey] section of config_build.toml.");
+
         ui.separator(); // Separatore tra le sezioni
 
         // Sezione di gestione della configurazione
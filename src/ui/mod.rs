@@ -1,11 +1,22 @@
 pub mod analytics;
 pub mod backup;
+pub mod catalog;
 pub mod info;
 use backup::save_folders;
 use eframe::egui::{self, Color32, Ui};
 use serde::Serialize;
 use std::sync::mpsc::Sender;
-use crate::utils::{check_auto_start_status, read_config_file_display, set_display_true};
+use crate::catalog::{CatalogEntry, CatalogQuery};
+use catalog::CatalogRequest;
+use crate::diff::RunDiff;
+use crate::parity;
+use crate::queue::{self, QueuedJob};
+use crate::storage;
+use crate::replication::ReplicationReport;
+use crate::transfer::BackupReport;
+use crate::utils::{check_auto_start_status, load_config, read_config_file_display, set_display_true, VolumeConfig};
+use crate::volumes::{self, Volume};
+use crate::watcher::WatchStatus;
 
 use std::{
     process,
@@ -22,6 +33,7 @@ pub enum PanelType {
     #[default]
     Backup,
     Analytics,
+    Catalog,
     Info,
 }
 
@@ -40,18 +52,32 @@ pub enum InfoSource {
 pub enum BackupStatus {
     NotStarted,
     ToConfirm,
+    Countdown(u32), // gesto confermato: secondi mancanti all'avvio, annullabile con clic destro o Esc
+    ToOverride(String), // modifiche sospette nella sorgente: serve una conferma esplicita
     InProgress,
     CompletedSuccess,
     Canceled,
     CompletedError(String),
 }
 
+/// Operazione mostrata nella finestra di avanzamento
+#[derive(Serialize, PartialEq, Eq, Clone, Debug)]
+pub enum JobKind {
+    Backup,
+    Replication, // copia dei backup già fatti verso la destinazione di [replication]
+}
+
 // Application state, including the selected panel and configuration
 #[derive(Serialize)]
 pub struct AppState {
     current_panel: PanelType,
     source_folder: String,
     destination_folder: String,
+    remote_destination: Option<String>, // destinazione remota impostata in config_build.toml
+    use_volume: bool,                   // destinazione su un volume rimovibile invece che su una cartella
+    volume: VolumeConfig,
+    volumes: Vec<Volume>,               // volumi rimovibili collegati (aggiornati con "Refresh")
+    mount_trigger_enabled: bool,        // backup automatico al collegamento del volume
     backup_type: String,
     file_types: Vec<String>,
     new_file_type: String,        // for the backup panel
@@ -67,8 +93,32 @@ pub struct AppState {
     pub backup_status: BackupStatus,
     pub auto_start_enabled: bool,
     pub run_gui: bool,
+    parity_enabled: bool,                  // genera i dati di recupero ad ogni backup
+    pub last_report: Option<BackupReport>, // riepilogo dell'ultimo backup completato
+    pub scrub_alert: Option<String>,       // file corrotti trovati dalla verifica periodica
+    pub eject_volume: Option<Volume>,      // volume da proporre di espellere a fine backup automatico
+    pub eject_message: Option<String>,     // esito dell'espulsione
+    pub pending_jobs: Vec<QueuedJob>,      // backup rimandati in attesa della destinazione
+    pub job: JobKind,                      // operazione in corso o appena conclusa
+    pub last_replication: Option<ReplicationReport>,
+    replication_requested: bool,           // il Backup Panel chiede di avviare la replica
+    repair_requested: bool,                // il Backup Panel chiede di riparare la destinazione
+    repair_running: bool,                  // riparazione in corso in un thread separato
+    pub next_scheduled: Vec<String>,       // prossime esecuzioni programmate (aggiornate dallo scheduler)
+    pub watch: WatchStatus,                // stato della protezione continua (mostrato nel pannello Analytics)
+    pub backup_paused: bool,               // backup sospeso perché l'utente è tornato al computer
+    catalog_query: CatalogQuery,           // criteri di ricerca del pannello catalogo
+    catalog_results: Vec<CatalogEntry>,
+    catalog_message: Option<String>,       // esito dell'ultima ricerca o dell'ultimo ripristino
+    catalog_request: Option<CatalogRequest>, // operazione chiesta dal pannello catalogo
+    catalog_busy: bool,                    // operazione del pannello catalogo in corso in un thread separato
+    diff_runs: Option<Vec<String>>,        // backup della destinazione (letti una volta, poi con "Refresh")
+    diff_old_run: String,                  // backup da confrontare nel pannello catalogo
+    diff_new_run: String,
+    run_diff: Option<Result<RunDiff, String>>,
 }
 
+#[derive(Clone)]
 pub struct MyApp {
     pub state: Arc<Mutex<AppState>>,
     pub tx1: Sender<String>,       // Canale per comunicare col Detector
@@ -103,6 +153,11 @@ impl AppState {
                     current_panel: PanelType::Backup, // Pannello di default
                     source_folder,
                     destination_folder,
+                    remote_destination: load_config().and_then(|c| storage::remote_description(&c)),
+                    use_volume: load_config().map(|c| c.destination_type == "volume").unwrap_or(false),
+                    volume: load_config().map(|c| c.volume).unwrap_or_default(),
+                    volumes: volumes::removable_volumes(),
+                    mount_trigger_enabled: load_config().map(|c| c.mount_trigger.enabled).unwrap_or(false),
                     backup_type: if backup_type.is_empty() {
                         "total".to_string()
                     } else {
@@ -122,12 +177,40 @@ impl AppState {
                     backup_status: BackupStatus::NotStarted,
                     auto_start_enabled: check_auto_start_status(),
                     run_gui: read_config_file_display(),       //prendi il valore dalla configurazione config_build (display "statico")
+                    parity_enabled: load_config().map(|c| c.parity.enabled).unwrap_or(false),
+                    last_report: None,
+                    scrub_alert: None,
+                    eject_volume: None,
+                    eject_message: None,
+                    pending_jobs: queue::pending_jobs(),
+                    job: JobKind::Backup,
+                    last_replication: None,
+                    replication_requested: false,
+                    repair_requested: false,
+                    repair_running: false,
+                    next_scheduled: vec![],
+                    watch: WatchStatus::default(),
+                    backup_paused: false,
+                    catalog_query: CatalogQuery::default(),
+                    catalog_results: vec![],
+                    catalog_message: None,
+                    catalog_request: None,
+                    catalog_busy: false,
+                    diff_runs: None,
+                    diff_old_run: "".to_string(),
+                    diff_new_run: "".to_string(),
+                    run_diff: None,
                 }
             }
             _ => Self {
                 current_panel: PanelType::Backup, // Pannello di default
                 source_folder: "".to_string(),
                 destination_folder: "".to_string(),
+                remote_destination: load_config().and_then(|c| storage::remote_description(&c)),
+                use_volume: false,
+                volume: VolumeConfig::default(),
+                volumes: volumes::removable_volumes(),
+                mount_trigger_enabled: false,
                 backup_type: "total".to_string(),
                 file_types: vec![],
                 new_file_type: "".to_string(),
@@ -143,6 +226,29 @@ impl AppState {
                 backup_status: BackupStatus::NotStarted,
                 auto_start_enabled: check_auto_start_status(),
                 run_gui: read_config_file_display(),      // display "statico" --> default: true (la GUI all'inizio viene sempre mostrata)
+                parity_enabled: false,
+                last_report: None,
+                scrub_alert: None,
+                eject_volume: None,
+                eject_message: None,
+                pending_jobs: queue::pending_jobs(),
+                job: JobKind::Backup,
+                last_replication: None,
+                replication_requested: false,
+                repair_requested: false,
+                repair_running: false,
+                next_scheduled: vec![],
+                watch: WatchStatus::default(),
+                backup_paused: false,
+                catalog_query: CatalogQuery::default(),
+                catalog_results: vec![],
+                catalog_message: None,
+                catalog_request: None,
+                catalog_busy: false,
+                diff_runs: None,
+                diff_old_run: "".to_string(),
+                diff_new_run: "".to_string(),
+                run_diff: None,
             },
         }
     }
@@ -181,6 +287,8 @@ pub fn main_panel(ctx: &egui::Context, state: &mut MyApp) {
         show_info_modal = state.state.lock().unwrap().show_info_modal.clone();
     }
 
+    let tx1 = state.tx1.clone();
+    let shared_state = state.state.clone();
     let mut state = state.state.lock().unwrap(); // Accedi al Mutex
 
     render_sidebar(ctx, &mut *state);
@@ -193,6 +301,49 @@ pub fn main_panel(ctx: &egui::Context, state: &mut MyApp) {
     if show_info_modal {
         render_success_modal(ctx, &mut *state);
     }
+
+    // La replica viene avviata dal detector, che gestisce i job in background
+    if state.replication_requested {
+        state.replication_requested = false;
+        if let Err(err) = tx1.send("startReplication".to_string()) {
+            eprintln!("Failed to send replication request: {}", err);
+        }
+    }
+    if let Some(request) = state.catalog_request.take() {
+        catalog::start_request(&mut state, request, shared_state.clone());
+    }
+    if state.repair_requested {
+        state.repair_requested = false;
+        start_repair(&mut state, shared_state);
+    }
+}
+
+/// Ripara la destinazione in un thread separato: su destinazioni grandi o remote richiede tempo
+/// e la GUI (come gli altri thread che usano lo stato) non deve restare bloccata
+fn start_repair(app_state: &mut AppState, shared_state: Arc<Mutex<AppState>>) {
+    if app_state.repair_running {
+        return;
+    }
+    app_state.repair_running = true;
+    thread::spawn(move || {
+        let repair = storage::open_configured().and_then(|backend| {
+            parity::repair_destination(backend.as_ref()).map_err(|e| e.to_string())
+        });
+        let mut app_state = shared_state.lock().unwrap();
+        app_state.repair_running = false;
+        match repair {
+            Ok(report) => {
+                app_state.info_message = Some(report.summary());
+                app_state.info_source = Some(InfoSource::Success);
+                app_state.show_info_modal = true;
+            }
+            Err(e) => {
+                app_state.error_message = Some(format!("Repair failed: {}", e));
+                app_state.error_source = Some(ErrorSource::SaveOperation);
+                app_state.show_error_modal = true;
+            }
+        }
+    });
 }
 
 fn render_sidebar(ctx: &egui::Context, state: &mut AppState) {
@@ -225,6 +376,12 @@ fn render_sidebar(ctx: &egui::Context, state: &mut AppState) {
                 {
                     state.current_panel = PanelType::Analytics;
                 }
+                if ui
+                    .selectable_label(state.current_panel == PanelType::Catalog, "Catalog Panel")
+                    .clicked()
+                {
+                    state.current_panel = PanelType::Catalog;
+                }
                 if ui
                     .selectable_label(state.current_panel == PanelType::Info, "Info Panel")
                     .clicked()
@@ -254,7 +411,8 @@ fn render_main_content(ctx: &egui::Context, state: &mut AppState) {
         }
         match state.current_panel {
             PanelType::Backup => backup::show_backup_panel(ui, state),
-            PanelType::Analytics => analytics::show_analytics_panel(ui),
+            PanelType::Analytics => analytics::show_analytics_panel(ui, state),
+            PanelType::Catalog => catalog::show_catalog_panel(ui, state),
             PanelType::Info => info::show_info_panel(ui, state),
         }
     });
@@ -386,6 +544,37 @@ pub fn render_modal_exit(ctx: &egui::Context, state: &mut MyApp, frame: &mut efr
     }
 }
 
+pub fn render_scrub_alert(ctx: &egui::Context, state: &mut MyApp) {
+    let shared_state = state.state.clone();
+    let mut app_state = state.state.lock().unwrap();
+    let alert = match app_state.scrub_alert.clone() {
+        Some(alert) => alert,
+        None => return,
+    };
+
+    egui::Window::new("Backup Corruption Detected")
+        .collapsible(false)
+        .resizable(false)
+        .fixed_size(egui::vec2(400.0, 200.0))
+        .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+                ui.colored_label(Color32::LIGHT_RED, alert);
+            });
+
+            ui.horizontal(|ui| {
+                // La riparazione è possibile solo se i dati di recupero erano attivi durante il backup
+                if ui.button("Repair now").clicked() {
+                    app_state.scrub_alert = None;
+                    start_repair(&mut app_state, shared_state.clone());
+                }
+                if ui.button("Dismiss").clicked() {
+                    app_state.scrub_alert = None;
+                }
+            });
+        });
+}
+
 // Function to display the error message panel
 pub fn exit_panel(ctx: &egui::Context, _state: &MyApp, error_message: &str) {
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -405,20 +594,35 @@ pub fn exit_panel(ctx: &egui::Context, _state: &MyApp, error_message: &str) {
 pub fn show_backup_window(ctx: &egui::Context, state: &mut MyApp) {
     let backup_status;
     let show_confirmation_modal;
+    let last_report;
+    let job;
+    let last_replication;
     {
         let app_state = state.state.lock().unwrap();
         // Copia il valore di backup_status in una variabile separata
         backup_status = app_state.backup_status.clone();
         show_confirmation_modal = app_state.show_confirmation_modal.clone();
+        last_report = app_state.last_report.clone();
+        job = app_state.job.clone();
+        last_replication = app_state.last_replication.clone();
     }
 
     // Determina il titolo e il messaggio in base allo stato del backup
+    let countdown_message;
     let (title, message, show_return_button) = match backup_status {
+        BackupStatus::Countdown(seconds) => {
+            countdown_message = format!(
+                "The backup starts in {} s. Right-click or press Escape to abort.",
+                seconds
+            );
+            ("Backup Starting", countdown_message.as_str(), false)
+        }
         BackupStatus::ToConfirm => (
             "Backup Confirmation",
-            "To start the backup service draw a horitzontal line.",
+            "To start the backup service draw a horitzontal line or hold the confirmation key chord.",
             true,
         ),
+        BackupStatus::ToOverride(ref warning) => ("Suspicious Changes Detected", warning.as_str(), false),
         BackupStatus::InProgress => (
             "Backup In Progress",
             "The backup is currently running...",
@@ -435,6 +639,22 @@ pub fn show_backup_window(ctx: &egui::Context, state: &mut MyApp) {
         ),
         BackupStatus::NotStarted => return,
     };
+    // La replica usa la stessa finestra, con titoli propri
+    let (title, message) = match (&job, &backup_status) {
+        (JobKind::Replication, BackupStatus::InProgress) => (
+            "Replication In Progress",
+            "Copying the missing backups to the replication target...",
+        ),
+        (JobKind::Replication, BackupStatus::CompletedSuccess) => {
+            ("Replication Completed", "Replication completed successfully!")
+        }
+        (JobKind::Replication, BackupStatus::CompletedError(_)) => ("Replication Failed", message),
+        (JobKind::Replication, BackupStatus::Canceled) => (
+            "Replication Cancellation",
+            "The replication is being canceled. Please wait...",
+        ),
+        _ => (title, message),
+    };
 
     // Disegna il pannello centrale
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -449,6 +669,42 @@ pub fn show_backup_window(ctx: &egui::Context, state: &mut MyApp) {
             if backup_status == BackupStatus::ToConfirm {
                 ui.label("Otherwise, press the button below to cancel the backup routine.");
             }
+            if let BackupStatus::Countdown(_) = backup_status {
+                ui.add_space(10.0);
+                if ui.button("Abort").clicked() {
+                    // Il thread del conto alla rovescia vede il cambio di stato e non avvia il backup
+                    state.state.lock().unwrap().backup_status = BackupStatus::NotStarted;
+                }
+            }
+
+            // Riepilogo della replica appena completata
+            if backup_status == BackupStatus::CompletedSuccess && job == JobKind::Replication {
+                if let Some(report) = &last_replication {
+                    ui.add_space(10.0);
+                    for line in report.summary_lines() {
+                        ui.label(line);
+                    }
+                }
+            }
+
+            // Riepilogo del backup appena completato
+            if backup_status == BackupStatus::CompletedSuccess && job == JobKind::Backup {
+                if let Some(report) = &last_report {
+                    ui.add_space(10.0);
+                    for line in report.summary_lines() {
+                        ui.label(line);
+                    }
+                }
+            }
+
+            // Backup partito dal collegamento del volume: propone di espellerlo
+            if backup_status == BackupStatus::CompletedSuccess {
+                render_eject_prompt(ui, state);
+            }
+
+            if let BackupStatus::ToOverride(_) = backup_status {
+                render_override_request(ui, state);
+            }
 
             /* Gestione schermata per backup in progress */
             if backup_status == BackupStatus::InProgress {
@@ -468,12 +724,55 @@ pub fn show_backup_window(ctx: &egui::Context, state: &mut MyApp) {
                     // Aggiorna lo stato solo quando il pulsante viene cliccato
                     let mut app_state = state.state.lock().unwrap();
                     app_state.backup_status = BackupStatus::NotStarted;
+                    app_state.eject_volume = None;
+                    app_state.eject_message = None;
                 }
             }
         });
     });
 }
 
+fn render_eject_prompt(ui: &mut Ui, state: &mut MyApp) {
+    let mut app_state = state.state.lock().unwrap();
+    if let Some(volume) = app_state.eject_volume.clone() {
+        ui.add_space(10.0);
+        ui.label(format!("Do you want to eject {} now?", volume.display_name()));
+        if ui.button("Eject").clicked() {
+            app_state.eject_message = Some(match volumes::eject(&volume) {
+                Ok(()) => "The volume can now be safely removed.".to_string(),
+                Err(e) => e,
+            });
+            app_state.eject_volume = None;
+        }
+    }
+    if let Some(message) = &app_state.eject_message {
+        ui.add_space(10.0);
+        ui.label(message);
+    }
+}
+
+fn render_override_request(ui: &mut Ui, state: &mut MyApp) {
+    ui.add_space(10.0);
+    ui.colored_label(
+        Color32::LIGHT_RED,
+        "The backup is paused. Check the source folder before overwriting the previous copies.",
+    );
+    ui.horizontal(|ui| {
+        if ui.button("Override and continue").clicked() {
+            if let Err(err) = state.tx_stop.send("override".to_string()) {
+                eprintln!("Failed to send override message to backup thread: {}", err);
+            }
+        }
+        if ui.button("Cancel backup").clicked() {
+            if let Err(err) = state.tx_stop.send("stop".to_string()) {
+                eprintln!("Failed to send stop message to backup thread: {}", err);
+            }
+            let mut app_state = state.state.lock().unwrap();
+            app_state.backup_status = BackupStatus::Canceled;
+        }
+    });
+}
+
 fn render_backup_progress(ui: &mut Ui, state: &mut MyApp) {
     ui.add_space(10.0);
     ui.label("Click \"Stop\" to abort the backup");
@@ -493,6 +792,12 @@ fn render_backup_progress(ui: &mut Ui, state: &mut MyApp) {
             app_state.backup_status = BackupStatus::Canceled;
         }
     }
+    if state.state.lock().unwrap().backup_paused {
+        ui.colored_label(
+            Color32::YELLOW,
+            "Paused while you are using the computer: the backup resumes when you step away.",
+        );
+    }
     // Mostra il percorso completo del file corrente
     if let Some(current_file) = state.current_file.lock().unwrap().clone() {
         ui.label(format!("Copying file:: {}", current_file));
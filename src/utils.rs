@@ -1,6 +1,6 @@
 use eframe::IconData;
 use image::RgbaImage;
-use rodio::{source::Source, Decoder, OutputStream};
+use rodio::{source::SineWave, source::Source, Decoder, OutputStream};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::fs::{self, OpenOptions};
@@ -214,13 +214,488 @@ pub fn play_sound(file_path: &str) {
         }
     });
 }
-#[derive(Deserialize, Debug, Serialize)]
-struct Config {
-    source_folder: String,
-    destination_folder: String,
-    backup_type: String,
-    file_types: Vec<String>,
-    display: bool
+/// Bip breve generato al momento, usato dal conto alla rovescia prima del backup
+pub fn beep() {
+    std::thread::spawn(|| {
+        if let Ok((_stream, stream_handle)) = OutputStream::try_default() {
+            let tone = SineWave::new(880.0)
+                .take_duration(Duration::from_millis(150))
+                .amplify(0.3);
+            if stream_handle.play_raw(tone.convert_samples()).is_ok() {
+                std::thread::sleep(Duration::from_millis(200)); // Mantieni il thread attivo
+            }
+        }
+    });
+}
+/// Annuncia un messaggio con la sintesi vocale del sistema, se disponibile (altrimenti non fa nulla)
+pub fn speak(text: &str) {
+    let text = text.to_string();
+    std::thread::spawn(move || {
+        #[cfg(windows)]
+        let spoken = std::process::Command::new("powershell")
+            .args([
+                "-NoProfile",
+                "-Command",
+                &format!(
+                    "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak('{}')",
+                    text.replace('\'', "''")
+                ),
+            ])
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        #[cfg(not(windows))]
+        let spoken = ["spd-say", "espeak"].iter().any(|program| {
+            std::process::Command::new(program)
+                .arg(&text)
+                .status()
+                .map(|status| status.success())
+                .unwrap_or(false)
+        });
+        if !spoken {
+            println!("Sintesi vocale non disponibile: {}", text);
+        }
+    });
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+pub struct Config {
+    pub source_folder: String,
+    pub destination_folder: String,
+    pub backup_type: String,
+    pub file_types: Vec<String>,
+    pub display: bool,
+    #[serde(default)]
+    pub destination_type: String, // tipo di destinazione: "local" (vuoto = local) "volume", "sftp", "s3", "webdav", "peer"
+    #[serde(default)]
+    pub volume: VolumeConfig, // usata solo se destination_type = "volume"
+    #[serde(default)]
+    pub sftp: SftpConfig, // usata solo se destination_type = "sftp"
+    #[serde(default)]
+    pub s3: S3Config, // usata solo se destination_type = "s3"
+    #[serde(default)]
+    pub webdav: WebDavConfig, // usata solo se destination_type = "webdav"
+    #[serde(default)]
+    pub peer: PeerConfig, // usata se destination_type = "peer" e dalla modalità ricevitore
+    #[serde(default)]
+    pub fallback: Vec<ExtraDestination>, // destinazioni di riserva, provate in ordine se la principale non è raggiungibile
+    #[serde(default)]
+    pub replication: ExtraDestination, // destinazione su cui copiare i backup già fatti (vuota = nessuna replica)
+    #[serde(default)]
+    pub schedule: Vec<ScheduleConfig>, // job eseguiti automaticamente dal processo in background
+    #[serde(default)]
+    pub parity: ParityConfig, // dati di recupero (Reed-Solomon) generati ad ogni backup
+    #[serde(default)]
+    pub scrub: ScrubConfig, // verifica periodica dei backup salvati
+    #[serde(default)]
+    pub guard: GuardConfig, // protezione contro modifiche di massa (es. ransomware)
+    #[serde(default)]
+    pub mount_trigger: MountTriggerConfig, // backup automatico al collegamento del volume di destinazione
+    #[serde(default)]
+    pub catch_up: CatchUpConfig, // recupero all'avvio dei job programmati saltati mentre il PC era spento
+    #[serde(default)]
+    pub watch: WatchConfig, // protezione continua: copia i file appena modificati
+    #[serde(default)]
+    pub idle: IdleConfig, // backup avviato quando l'utente non usa il computer
+    #[serde(default)]
+    pub shutdown_backup: ShutdownBackupConfig, // backup rapido prima dello spegnimento o della fine della sessione
+    #[serde(default)]
+    pub power: PowerConfig, // job non urgenti rimandati quando il portatile va a batteria
+    #[serde(default)]
+    pub battery_emergency: BatteryEmergencyConfig, // backup automatico quando la batteria sta per esaurirsi
+    #[serde(default)]
+    pub cpu_gate: CpuGateConfig, // backup programmati e per inattività rimandati se la CPU è occupata
+    #[serde(default)]
+    pub hotkey: HotkeyConfig, // combinazioni di tasti alternative al gesto
+    #[serde(default)]
+    pub countdown: CountdownConfig, // conto alla rovescia annullabile dopo la conferma del gesto
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            source_folder: "".to_string(),
+            destination_folder: "".to_string(),
+            backup_type: "total".to_string(),
+            file_types: vec![],
+            display: true,
+            destination_type: "local".to_string(),
+            volume: VolumeConfig::default(),
+            sftp: SftpConfig::default(),
+            s3: S3Config::default(),
+            webdav: WebDavConfig::default(),
+            peer: PeerConfig::default(),
+            fallback: vec![],
+            replication: ExtraDestination::default(),
+            schedule: vec![],
+            parity: ParityConfig::default(),
+            scrub: ScrubConfig::default(),
+            guard: GuardConfig::default(),
+            mount_trigger: MountTriggerConfig::default(),
+            catch_up: CatchUpConfig::default(),
+            watch: WatchConfig::default(),
+            idle: IdleConfig::default(),
+            shutdown_backup: ShutdownBackupConfig::default(),
+            power: PowerConfig::default(),
+            battery_emergency: BatteryEmergencyConfig::default(),
+            cpu_gate: CpuGateConfig::default(),
+            hotkey: HotkeyConfig::default(),
+            countdown: CountdownConfig::default(),
+        }
+    }
+}
+
+/// Destinazione su un volume rimovibile, riconosciuto dall'UUID (o dall'etichetta) invece che dal punto di montaggio
+#[derive(Deserialize, Debug, Serialize, Clone, Default, PartialEq)]
+#[serde(default)] // i campi mancanti nella sezione prendono il valore predefinito
+pub struct VolumeConfig {
+    pub uuid: String,
+    pub label: String,         // usata solo se l'UUID è vuoto
+    pub relative_path: String, // cartella di destinazione all'interno del volume
+}
+
+/// Destinazione diversa dalla principale (sezioni [[fallback]] e [replication]): una cartella,
+/// un volume o uno dei tipi remoti, che usano la rispettiva sezione della configurazione
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+pub struct ExtraDestination {
+    #[serde(default)]
+    pub name: String, // nome annunciato quando viene usata (es. "second disk")
+    #[serde(default)]
+    pub destination_type: String,
+    #[serde(default)]
+    pub destination_folder: String,
+    #[serde(default)]
+    pub volume: VolumeConfig,
+}
+
+/// Job programmato (sezioni [[schedule]])
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+pub struct ScheduleConfig {
+    pub when: String, // "daily at 02:00", "every 4h" o espressione cron (es. "0 18 * * 5")
+    #[serde(default)]
+    pub job: String,  // "backup" (predefinito) o "replication"
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct SftpConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub key_file: String,       // chiave privata usata per l'autenticazione
+    pub key_passphrase: String, // vuota se la chiave non è protetta
+    pub remote_path: String,    // cartella di destinazione sul server
+}
+
+impl Default for SftpConfig {
+    fn default() -> Self {
+        SftpConfig {
+            host: "".to_string(),
+            port: 22,
+            user: "".to_string(),
+            key_file: "".to_string(),
+            key_passphrase: "".to_string(),
+            remote_path: "".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct S3Config {
+    pub endpoint: String, // es. https://s3.amazonaws.com oppure http://localhost:9000 per MinIO
+    pub bucket: String,
+    pub prefix: String,   // cartella virtuale dentro il bucket (può essere vuota)
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    pub part_size_mb: u64, // i file più grandi vengono caricati in più parti (minimo 5)
+}
+
+impl Default for S3Config {
+    fn default() -> Self {
+        S3Config {
+            endpoint: "".to_string(),
+            bucket: "".to_string(),
+            prefix: "".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: "".to_string(),
+            secret_key: "".to_string(),
+            part_size_mb: 8,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct WebDavConfig {
+    pub url: String, // URL della cartella di destinazione, es. https://cloud.example.com/remote.php/dav/files/utente/Backup
+    pub user: String,
+    pub password: String,
+    pub auth: String, // "basic" oppure "digest"
+}
+
+impl Default for WebDavConfig {
+    fn default() -> Self {
+        WebDavConfig {
+            url: "".to_string(),
+            user: "".to_string(),
+            password: "".to_string(),
+            auth: "basic".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct PeerConfig {
+    pub host: String, // indirizzo del PC ricevitore (vuoto in modalità ricevitore)
+    pub port: u16,    // porta del ricevitore, usata anche dalla modalità ricevitore
+    pub key: String,  // chiave condivisa, uguale sui due PC
+}
+
+impl Default for PeerConfig {
+    fn default() -> Self {
+        PeerConfig {
+            host: "".to_string(),
+            port: 7524,
+            key: "".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct ParityConfig {
+    pub enabled: bool,
+    pub redundancy: u8, // percentuale di blocchi di parità rispetto ai blocchi di dati
+}
+
+impl Default for ParityConfig {
+    fn default() -> Self {
+        ParityConfig {
+            enabled: false,
+            redundancy: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct ScrubConfig {
+    pub enabled: bool,
+    pub interval_hours: u64, // ore tra due verifiche consecutive
+}
+
+impl Default for ScrubConfig {
+    fn default() -> Self {
+        ScrubConfig {
+            enabled: false,
+            interval_hours: 24,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct GuardConfig {
+    pub enabled: bool,
+    pub min_files: usize,          // sotto questo numero di file il controllo non viene fatto
+    pub changed_ratio: f32,        // quota di file modificati o spariti (0.0 - 1.0)
+    pub entropy_ratio: f32,        // quota dei file modificati con contenuto ad alta entropia
+    pub new_extension_ratio: f32,  // quota di file con estensioni nuove
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        GuardConfig {
+            enabled: false,
+            min_files: 20,
+            changed_ratio: 0.5,
+            entropy_ratio: 0.3,
+            new_extension_ratio: 0.3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct MountTriggerConfig {
+    pub enabled: bool,      // avvia il backup quando viene collegato il volume della sezione [volume]
+    pub eject_prompt: bool, // a backup completato propone di espellere il volume
+}
+
+impl Default for MountTriggerConfig {
+    fn default() -> Self {
+        MountTriggerConfig {
+            enabled: false,
+            eject_prompt: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct CatchUpConfig {
+    pub enabled: bool,      // all'avvio esegue i job la cui programmazione è scaduta a PC spento
+    pub delay_minutes: i64, // attesa dopo l'accesso prima di recuperarli (0 = subito)
+}
+
+impl Default for CatchUpConfig {
+    fn default() -> Self {
+        CatchUpConfig {
+            enabled: true,
+            delay_minutes: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct WatchConfig {
+    pub enabled: bool,
+    pub folders: Vec<String>, // sottocartelle della sorgente da sorvegliare (vuoto = tutta la sorgente)
+    pub delay_secs: u64,      // entro quanti secondi da una modifica il file viene copiato
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            enabled: false,
+            folders: vec![],
+            delay_secs: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct IdleConfig {
+    pub enabled: bool,
+    pub minutes: u64,      // minuti senza input di mouse o tastiera prima di avviare il backup
+    pub on_return: String, // al ritorno dell'utente: "pause" (riprende alla prossima assenza) o "stop"
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        IdleConfig {
+            enabled: false,
+            minutes: 30,
+            on_return: "pause".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct ShutdownBackupConfig {
+    pub enabled: bool,
+    pub time_budget_secs: u64, // tempo massimo concesso al backup finale
+}
+
+impl Default for ShutdownBackupConfig {
+    fn default() -> Self {
+        ShutdownBackupConfig {
+            enabled: false,
+            time_budget_secs: 20,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub defer_on_battery: bool,   // rimanda backup programmati, per inattività e in coda finché torna la corrente
+    pub min_battery_percent: f32, // sopra questa carica i job partono anche a batteria
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig {
+            defer_on_battery: true,
+            min_battery_percent: 50.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct BatteryEmergencyConfig {
+    pub enabled: bool,
+    pub critical_percent: f32,         // carica sotto cui parte il backup di emergenza (a batteria)
+    pub time_budget_secs: u64,         // tempo massimo concesso al backup
+    pub priority_folders: Vec<String>, // sottocartelle della sorgente da salvare per prime
+}
+
+impl Default for BatteryEmergencyConfig {
+    fn default() -> Self {
+        BatteryEmergencyConfig {
+            enabled: false,
+            critical_percent: 10.0,
+            time_budget_secs: 120,
+            priority_folders: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(default)]
+pub struct CpuGateConfig {
+    pub enabled: bool,
+    pub max_load_percent: f32, // il job parte quando il carico medio scende sotto questa soglia
+    pub window_minutes: usize, // minuti su cui viene calcolata la media (un campione al minuto)
+    pub max_wait_minutes: u64, // dopo questa attesa il job parte comunque
+}
+
+impl Default for CpuGateConfig {
+    fn default() -> Self {
+        CpuGateConfig {
+            enabled: true,
+            max_load_percent: 50.0,
+            window_minutes: 3,
+            max_wait_minutes: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    pub enabled: bool,
+    pub chord: String,         // sostituisce il contorno dello schermo (es. "Ctrl+Alt+Shift+B")
+    pub confirm_chord: String, // sostituisce la linea orizzontale di conferma
+    pub hold_secs: f32,        // per quanto vanno tenuti premuti i tasti
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        HotkeyConfig {
+            enabled: false,
+            chord: "Ctrl+Alt+Shift+B".to_string(),
+            confirm_chord: "Ctrl+Alt+Shift+Y".to_string(),
+            hold_secs: 2.0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct CountdownConfig {
+    pub seconds: u32, // bip (uno al secondo) prima dell'avvio; 0 = il backup parte subito
+}
+
+/// Legge l'intero file di configurazione, incluse le sezioni opzionali.
+/// Restituisce `None` se il file non esiste o non è valido.
+pub fn load_config() -> Option<Config> {
+    let content = fs::read_to_string("config_build.toml").ok()?;
+    toml::from_str(&content).ok()
+}
+
+/// Salva l'intero file di configurazione, mantenendo le sezioni opzionali.
+pub fn store_config(config: &Config) -> io::Result<()> {
+    let content = toml::to_string(config)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+    fs::write("config_build.toml", content)
 }
 
 #[derive(Debug, Clone)]
@@ -275,8 +750,10 @@ display = true
     };
 
     // Verifica che tutti i campi siano rispettati
+    // Le destinazioni remote non usano `destination_folder`: i loro dati sono nelle rispettive sezioni
+    let local_destination = matches!(parsed.destination_type.as_str(), "" | "local");
     if parsed.source_folder.is_empty()
-        || parsed.destination_folder.is_empty()
+        || (local_destination && parsed.destination_folder.is_empty())
         || parsed.backup_type.is_empty()
     {
         // vuol dire che la configurazione non è completa, quindi il detector non può partire
@@ -0,0 +1,296 @@
+use crate::utils::VolumeConfig;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+/// Volume (partizione o chiavetta) attualmente montato
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Volume {
+    pub uuid: String,  // su Windows è il numero di serie del volume, nello stesso formato dei dischi FAT
+    pub label: String, // etichetta, può essere vuota
+    pub mount_point: PathBuf,
+    pub device: PathBuf, // dispositivo (es. /dev/sdb1, su Windows la radice dell'unità), usato per l'espulsione
+    pub removable: bool,
+}
+
+impl Volume {
+    /// Testo mostrato nell'elenco dei volumi del Backup Panel
+    pub fn display_name(&self) -> String {
+        let name = if self.label.is_empty() {
+            "(no label)"
+        } else {
+            self.label.as_str()
+        };
+        format!("{} [{}] - {}", name, self.uuid, self.mount_point.display())
+    }
+
+    fn matches(&self, config: &VolumeConfig) -> bool {
+        if !config.uuid.is_empty() {
+            self.uuid.eq_ignore_ascii_case(&config.uuid)
+        } else {
+            !config.label.is_empty() && self.label == config.label
+        }
+    }
+}
+
+/// Decodifica gli spazi e gli altri caratteri scritti in ottale in /proc/self/mountinfo (es. `\040`)
+#[cfg(not(windows))]
+fn unescape_mount_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() {
+            let octal = std::str::from_utf8(&bytes[i + 1..i + 4]).ok();
+            if let Some(byte) = octal.and_then(|octal| u8::from_str_radix(octal, 8).ok()) {
+                decoded.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Associa ogni dispositivo (percorso canonico, es. /dev/sdb1) al nome del suo link in /dev/disk/by-*
+#[cfg(not(windows))]
+fn device_links(folder: &str) -> std::collections::HashMap<PathBuf, String> {
+    let mut links = std::collections::HashMap::new();
+    if let Ok(entries) = fs::read_dir(folder) {
+        for entry in entries.flatten() {
+            if let Ok(device) = fs::canonicalize(entry.path()) {
+                let name = unescape_udev_name(&entry.file_name().to_string_lossy());
+                links.insert(device, name);
+            }
+        }
+    }
+    links
+}
+
+/// udev scrive i caratteri speciali delle etichette come `\x20`
+#[cfg(not(windows))]
+fn unescape_udev_name(name: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find("\\x") {
+        decoded.push_str(&rest[..start]);
+        match rest.get(start + 2..start + 4).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                decoded.push(byte as char);
+                rest = &rest[start + 4..];
+            }
+            None => {
+                decoded.push_str("\\x");
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Un disco è rimovibile se il kernel lo segnala o se è montato automaticamente sotto /media o /run/media
+#[cfg(not(windows))]
+fn is_removable(device: &std::path::Path, mount_point: &str) -> bool {
+    if mount_point.starts_with("/media/") || mount_point.starts_with("/run/media/") {
+        return true;
+    }
+    let Some(name) = device.file_name() else {
+        return false;
+    };
+    let Ok(sys_path) = fs::canonicalize(PathBuf::from("/sys/class/block").join(name)) else {
+        return false;
+    };
+    // Per una partizione il flag si trova nella cartella del disco che la contiene
+    [sys_path.join("removable"), sys_path.join("../removable")]
+        .iter()
+        .any(|flag| fs::read_to_string(flag).map(|v| v.trim() == "1").unwrap_or(false))
+}
+
+/// Volumi montati con UUID o etichetta, letti da /proc/self/mountinfo
+#[cfg(not(windows))]
+pub fn mounted_volumes() -> Vec<Volume> {
+    let Ok(mountinfo) = fs::read_to_string("/proc/self/mountinfo") else {
+        return vec![];
+    };
+    let uuids = device_links("/dev/disk/by-uuid");
+    let labels = device_links("/dev/disk/by-label");
+
+    let mut volumes: Vec<Volume> = vec![];
+    for line in mountinfo.lines() {
+        // Formato: id padre maj:min radice punto_di_montaggio opzioni [campi opzionali] - tipo sorgente opzioni
+        let Some((left, right)) = line.split_once(" - ") else {
+            continue;
+        };
+        let fields: Vec<&str> = left.split_whitespace().collect();
+        let source = right.split_whitespace().nth(1).unwrap_or("");
+        if fields.len() < 5 || fields[3] != "/" || !source.starts_with("/dev/") {
+            continue; // solo montaggi dell'intero filesystem di un dispositivo
+        }
+        let Ok(device) = fs::canonicalize(unescape_mount_path(source)) else {
+            continue;
+        };
+        let uuid = uuids.get(&device).cloned().unwrap_or_default();
+        let label = labels.get(&device).cloned().unwrap_or_default();
+        if uuid.is_empty() && label.is_empty() {
+            continue;
+        }
+        let mount_point = unescape_mount_path(fields[4]);
+        if volumes.iter().any(|v| v.uuid == uuid && v.label == label) {
+            continue; // stesso volume montato più volte
+        }
+        volumes.push(Volume {
+            removable: is_removable(&device, &mount_point),
+            uuid,
+            label,
+            mount_point: PathBuf::from(mount_point),
+            device,
+        });
+    }
+    volumes
+}
+
+/// Unità con una lettera assegnata; l'identificativo è il numero di serie del volume
+#[cfg(windows)]
+pub fn mounted_volumes() -> Vec<Volume> {
+    use std::ptr;
+    use winapi::um::fileapi::{GetDriveTypeW, GetLogicalDrives, GetVolumeInformationW};
+    use winapi::um::winbase::DRIVE_REMOVABLE;
+
+    let mut volumes = vec![];
+    let drives = unsafe { GetLogicalDrives() };
+    for index in 0..26u32 {
+        if drives & (1 << index) == 0 {
+            continue;
+        }
+        let root = format!("{}:\\", (b'A' + index as u8) as char);
+        let wide: Vec<u16> = root.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut label = [0u16; 261];
+        let mut serial: u32 = 0;
+        let ok = unsafe {
+            GetVolumeInformationW(
+                wide.as_ptr(),
+                label.as_mut_ptr(),
+                label.len() as u32,
+                &mut serial,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if ok == 0 {
+            continue; // unità senza supporto (es. lettore vuoto)
+        }
+        let length = label.iter().position(|&c| c == 0).unwrap_or(label.len());
+        volumes.push(Volume {
+            uuid: format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF),
+            label: String::from_utf16_lossy(&label[..length]),
+            mount_point: PathBuf::from(&root),
+            device: PathBuf::from(&root),
+            removable: unsafe { GetDriveTypeW(wide.as_ptr()) } == DRIVE_REMOVABLE,
+        });
+    }
+    volumes
+}
+
+/// Volumi rimovibili collegati, proposti come destinazione nel Backup Panel
+pub fn removable_volumes() -> Vec<Volume> {
+    mounted_volumes().into_iter().filter(|v| v.removable).collect()
+}
+
+/// Volume configurato, se è collegato
+pub fn find(config: &VolumeConfig) -> Option<Volume> {
+    mounted_volumes().into_iter().find(|volume| volume.matches(config))
+}
+
+/// Trova il punto di montaggio attuale del volume configurato e restituisce la cartella di destinazione
+pub fn resolve(config: &VolumeConfig) -> Result<PathBuf, String> {
+    if config.uuid.is_empty() && config.label.is_empty() {
+        return Err("Destination volume is not set: choose one from the Backup Panel.".to_string());
+    }
+    let volume = find(config)
+        .ok_or_else(|| format!("Destination volume `{}` is not connected.", describe(config)))?;
+
+    let mut folder = volume.mount_point;
+    for component in config.relative_path.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        folder.push(component);
+    }
+    // La cartella sul volume appartiene al backup: se manca viene creata
+    fs::create_dir_all(&folder).map_err(|e| {
+        format!(
+            "Unable to create `{}` on volume `{}`: {}",
+            folder.display(),
+            describe(config),
+            e
+        )
+    })?;
+    Ok(folder)
+}
+
+/// Descrizione stabile della destinazione, indipendente dal punto di montaggio
+pub fn describe(config: &VolumeConfig) -> String {
+    let id = if config.uuid.is_empty() {
+        format!("label={}", config.label)
+    } else {
+        format!("uuid={}", config.uuid)
+    };
+    format!("volume:{}/{}", id, config.relative_path.trim_matches(['/', '\\']))
+}
+
+/// Smonta il volume (e spegne il disco se possibile) perché possa essere scollegato in sicurezza
+#[cfg(not(windows))]
+pub fn eject(volume: &Volume) -> Result<(), String> {
+    use std::process::Command;
+
+    // udisks non richiede i privilegi di root per i dischi rimovibili; in alternativa si prova umount
+    let unmounted = Command::new("udisksctl")
+        .arg("unmount")
+        .arg("-b")
+        .arg(&volume.device)
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+        || Command::new("umount")
+            .arg(&volume.mount_point)
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+    if !unmounted {
+        return Err(format!(
+            "Unable to eject `{}`: the volume is busy or the operation is not permitted.",
+            volume.display_name()
+        ));
+    }
+    // Lo spegnimento non è indispensabile: il volume è già smontato
+    let _ = Command::new("udisksctl")
+        .arg("power-off")
+        .arg("-b")
+        .arg(&volume.device)
+        .status();
+    Ok(())
+}
+
+/// Espelle l'unità con lo stesso comando di "Espelli" di Esplora risorse
+#[cfg(windows)]
+pub fn eject(volume: &Volume) -> Result<(), String> {
+    use std::process::Command;
+
+    let drive = volume.mount_point.to_string_lossy().trim_end_matches('\\').to_string();
+    let script = format!(
+        "(New-Object -ComObject Shell.Application).Namespace(17).ParseName('{}').InvokeVerb('Eject')",
+        drive
+    );
+    let ejected = Command::new("powershell")
+        .args(["-NoProfile", "-Command", &script])
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if ejected {
+        Ok(())
+    } else {
+        Err(format!("Unable to eject `{}`.", volume.display_name()))
+    }
+}
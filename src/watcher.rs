@@ -0,0 +1,214 @@
+use crate::analytics::log_guard_event_to_csv;
+use crate::catalog;
+use crate::detector::BackupLauncher;
+use crate::guard;
+use crate::manifest::{self, RunManifest};
+use crate::storage;
+use crate::transfer::{copy_single_file, is_included};
+use crate::ui::BackupStatus;
+use crate::utils::{load_config, Config};
+use chrono::Local;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Attesa dopo una copia non riuscita (es. destinazione scollegata) prima di riprovare
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Stato della protezione continua, mostrato nel pannello Analytics
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct WatchStatus {
+    pub active: bool,
+    pub roots: Vec<String>,        // cartelle sorvegliate
+    pub pending: Vec<String>,      // file modificati in attesa di essere copiati
+    pub last_sync: Option<String>, // data e ora dell'ultima copia riuscita
+    pub files_synced: u64,         // file copiati dall'avvio del programma
+    pub error: Option<String>,     // ultimo problema (es. destinazione non raggiungibile)
+}
+
+/// Cartelle da sorvegliare secondo la configurazione
+fn watched_roots(config: &Config) -> Vec<PathBuf> {
+    let source = Path::new(&config.source_folder);
+    if !config.watch.enabled || config.source_folder.is_empty() || !source.is_dir() {
+        return vec![];
+    }
+    if config.watch.folders.is_empty() {
+        return vec![source.to_path_buf()];
+    }
+    config
+        .watch
+        .folders
+        .iter()
+        .map(|folder| source.join(folder))
+        .filter(|root| root.starts_with(source) && root.is_dir())
+        .collect()
+}
+
+/// Avvia il watcher del sistema (inotify su Linux) sulle cartelle indicate
+fn start_watching(roots: &[PathBuf], tx: Sender<notify::Result<Event>>) -> Option<RecommendedWatcher> {
+    if roots.is_empty() {
+        return None;
+    }
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Impossibile avviare la protezione continua: {}", e);
+            return None;
+        }
+    };
+    for root in roots {
+        if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+            eprintln!("Impossibile sorvegliare {:?}: {}", root, e);
+        }
+    }
+    println!("Protezione continua attiva su {:?}", roots);
+    Some(watcher)
+}
+
+/// Esito di una copia della protezione continua
+enum SyncOutcome {
+    Copied(u64),
+    Suspicious(String), // nessun file copiato: le modifiche vanno confermate con un backup completo
+}
+
+/// Copia nella destinazione principale i file modificati e li registra in un manifest,
+/// come un backup incrementale che contiene solo quei file
+fn sync_files(config: &Config, files: &[PathBuf]) -> Result<SyncOutcome, String> {
+    let backend = storage::open_destination(config)?;
+    // Anche una raffica piccola non deve sovrascrivere le copie buone se la sorgente è stata
+    // alterata: il controllo considera tutte le modifiche dall'ultimo backup completo
+    if config.guard.enabled {
+        let include = |path: &Path| is_included(config, path);
+        let source = Path::new(&config.source_folder);
+        match guard::check_mass_change(source, backend.as_ref(), &include, &config.guard) {
+            Ok(Some(warning)) => return Ok(SyncOutcome::Suspicious(warning)),
+            Ok(None) => {}
+            Err(e) => eprintln!("Controllo delle modifiche di massa non riuscito: {}", e),
+        }
+    }
+    let latest = manifest::latest_entries(backend.as_ref()).unwrap_or_default();
+    let mut run = RunManifest {
+        run_id: manifest::new_run_id(),
+        source_folder: config.source_folder.clone(),
+        files: vec![],
+        partial: true,
+    };
+    let fail = |e: io::Error| format!("Continuous backup failed: {}", e);
+
+    for path in files {
+        // Il file può essere stato cancellato o rinominato dopo la modifica
+        if !path.is_file() {
+            continue;
+        }
+        let entry = copy_single_file(backend.as_ref(), config, path, &latest).map_err(fail)?;
+        println!("Protezione continua: copiato {}", entry.path);
+        run.files.push(entry);
+    }
+    if run.files.is_empty() {
+        return Ok(SyncOutcome::Copied(0));
+    }
+
+    manifest::write_manifest(backend.as_ref(), &run).map_err(fail)?;
+    if let Err(e) = catalog::record_run(&backend.describe(), &run) {
+        eprintln!("Impossibile aggiornare il catalogo dei backup: {}", e);
+    }
+    Ok(SyncOutcome::Copied(run.files.len() as u64))
+}
+
+/// Sorveglia le cartelle sorgente e copia i file modificati entro `delay_secs` secondi.
+/// Le raffiche di modifiche vengono raccolte e copiate insieme in un'unica operazione.
+pub fn run_watcher(launcher: BackupLauncher) {
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut _watcher: Option<RecommendedWatcher> = None; // va tenuto in vita finché serve
+    let mut roots: Vec<PathBuf> = vec![];
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new(); // file -> prima modifica non ancora copiata
+    let mut retry_at: Option<Instant> = None;
+    let mut status = WatchStatus::default();
+
+    loop {
+        let mut events = vec![];
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(event) => events.push(event),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        events.extend(rx.try_iter());
+
+        // La configurazione viene riletta per applicare le modifiche fatte dalla GUI
+        let Some(config) = load_config() else {
+            continue;
+        };
+        let wanted = watched_roots(&config);
+        if wanted != roots {
+            _watcher = start_watching(&wanted, tx.clone());
+            status.active = _watcher.is_some();
+            status.roots = wanted.iter().map(|root| root.to_string_lossy().to_string()).collect();
+            roots = wanted;
+            pending.clear();
+        }
+
+        for event in events {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in event.paths {
+                        // I file interni dell'applicazione (es. una destinazione dentro la sorgente) non vanno copiati
+                        let internal = path.components().any(|c| c.as_os_str() == ".group24");
+                        if internal || !path.is_file() || !is_included(&config, &path) {
+                            continue;
+                        }
+                        pending.entry(path).or_insert_with(Instant::now);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Errore della protezione continua: {}", e),
+            }
+        }
+
+        // Si copia quando la modifica più vecchia in attesa raggiunge il ritardo configurato
+        let delay = Duration::from_secs(config.watch.delay_secs);
+        let due = pending.values().any(|since| since.elapsed() >= delay)
+            && !retry_at.is_some_and(|at| Instant::now() < at);
+        let busy = matches!(
+            launcher.state.lock().unwrap().backup_status,
+            BackupStatus::InProgress | BackupStatus::ToOverride(_)
+        );
+        if due && !busy {
+            let files: Vec<PathBuf> = pending.keys().cloned().collect();
+            match sync_files(&config, &files) {
+                Ok(SyncOutcome::Copied(count)) => {
+                    pending.clear();
+                    retry_at = None;
+                    status.files_synced += count;
+                    status.last_sync = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+                    status.error = None;
+                }
+                Ok(SyncOutcome::Suspicious(warning)) => {
+                    // Modifiche sospette: si passa da un backup completo, che chiede conferma all'utente
+                    log_guard_event_to_csv("held", &format!("continuous protection: {}", warning));
+                    if launcher.start(&format!("continuous protection: {} files changed", files.len())) {
+                        pending.clear();
+                    }
+                    retry_at = Some(Instant::now() + RETRY_DELAY);
+                    status.error = Some(warning);
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    retry_at = Some(Instant::now() + RETRY_DELAY);
+                    status.error = Some(e);
+                }
+            }
+        }
+
+        let source_root = Path::new(&config.source_folder);
+        status.pending = pending
+            .keys()
+            .map(|path| manifest::relative_path(source_root, path))
+            .collect();
+        status.pending.sort();
+        launcher.state.lock().unwrap().watch = status.clone();
+    }
+}